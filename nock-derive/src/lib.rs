@@ -0,0 +1,439 @@
+//! Derive macros for the `ToNoun` and `FromNoun` traits of the `nock` crate.
+//!
+//! Structs become right-nested cells of their fields, so `{a, b, c}` is
+//! `[a b c]`. A struct with one field is the noun of that field, and a
+//! struct without fields is `~`.
+//!
+//! Enums become tagged unions like Hoon's `$%`. A variant with fields is a
+//! cell of its tag and its fields, `[%tag a b]`, and a variant without
+//! fields is just its tag. The tag is a cord of the variant name in kebab
+//! case, so `FooBar` is `%foo-bar`.
+//!
+//! Attributes:
+//!
+//! - `#[noun(tag = "name")]` on a variant sets its tag.
+//! - `#[noun(flatten)]` on a field writes the fields of a struct into the
+//!   surrounding cell, so `{a, {b, c}, d}` is `[a b c d]` instead of
+//!   `[a [b c] d]`. The type of the field must be a struct that derives
+//!   the same traits.
+//!
+//! Enums can't be flattened, since their tag would have nowhere to go:
+//!
+//! ```compile_fail
+//! use nock_derive::ToNoun;
+//!
+//! #[derive(ToNoun)]
+//! enum Choice {
+//!     Yes,
+//!     No,
+//! }
+//!
+//! #[derive(ToNoun)]
+//! struct Answer {
+//!     id: u32,
+//!     #[noun(flatten)]
+//!     choice: Choice,
+//! }
+//! ```
+//!
+//! ```
+//! use nock::{FromNoun, Noun, ToNoun};
+//! use nock_derive::{FromNoun, ToNoun};
+//!
+//! #[derive(ToNoun, FromNoun, PartialEq, Debug)]
+//! enum Shape {
+//!     Point,
+//!     Circle { radius: u32 },
+//!     #[noun(tag = "box")]
+//!     Rectangle(u32, u32),
+//! }
+//!
+//! let noun: Noun = "[7.892.834 3 4]".parse().unwrap();
+//! assert_eq!(Shape::Rectangle(3, 4).to_noun(), noun);
+//! assert_eq!(Shape::from_noun(&noun), Ok(Shape::Rectangle(3, 4)));
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::{format_ident, quote};
+use proc_macro2::TokenTree;
+use syn::{parse_macro_input, parse_quote, Attribute, Data, DeriveInput,
+          Fields, Ident, LitStr, Path, Type};
+
+#[proc_macro_derive(ToNoun, attributes(noun))]
+pub fn derive_to_noun(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_noun(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+#[proc_macro_derive(FromNoun, attributes(noun))]
+pub fn derive_from_noun(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_noun(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// Options given with `#[noun(...)]`.
+#[derive(Default)]
+struct Options {
+    tag: Option<String>,
+    flatten: bool,
+}
+
+impl Options {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Options> {
+        let mut ret = Options::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("noun")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    let tag: LitStr = meta.value()?.parse()?;
+                    ret.tag = Some(tag.value());
+                    Ok(())
+                } else if meta.path.is_ident("flatten") {
+                    ret.flatten = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown noun attribute"))
+                }
+            })?;
+        }
+        Ok(ret)
+    }
+}
+
+/// Fields of a struct or a variant, bound to local variables.
+struct Binding {
+    /// Pattern or constructor with the fields as the variables.
+    pattern: Tokens,
+    vars: Vec<Ident>,
+    types: Vec<Type>,
+    flatten: Vec<bool>,
+}
+
+impl Binding {
+    fn new(path: Tokens, fields: &Fields) -> syn::Result<Binding> {
+        let vars: Vec<Ident> = (0..fields.len())
+                                   .map(|i| format_ident!("f{}", i))
+                                   .collect();
+        let flatten = fields.iter()
+                            .map(|f| Options::parse(&f.attrs))
+                            .map(|o| o.map(|o| o.flatten))
+                            .collect::<syn::Result<_>>()?;
+        let pattern = match fields {
+            Fields::Named(named) => {
+                let names = named.named.iter().map(|f| &f.ident);
+                quote!(#path { #(#names: #vars),* })
+            }
+            Fields::Unnamed(_) => quote!(#path(#(#vars),*)),
+            Fields::Unit => path,
+        };
+        Ok(Binding {
+            pattern,
+            vars,
+            types: fields.iter().map(|f| f.ty.clone()).collect(),
+            flatten,
+        })
+    }
+
+    /// Expression for the number of fields from field `from` on.
+    fn count(&self, from: usize) -> Tokens {
+        let plain = self.flatten[from..].iter().filter(|&&f| !f).count();
+        let flat = self.types[from..]
+                       .iter()
+                       .zip(&self.flatten[from..])
+                       .filter(|x| *x.1)
+                       .map(|x| x.0);
+        quote!(#plain #(+ <#flat as ::nock::fields::FromFields>::FIELDS)*)
+    }
+
+    /// Types of the flattened fields.
+    fn flat_types(&self) -> impl Iterator<Item = &Type> {
+        self.types.iter().zip(&self.flatten).filter(|x| *x.1).map(|x| x.0)
+    }
+
+    /// Statements that push the fields to `out`.
+    fn write(&self) -> Tokens {
+        let writes = self.vars.iter().zip(&self.flatten).map(|(v, &flat)| {
+            if flat {
+                quote!(::nock::fields::ToFields::to_fields(#v, out);)
+            } else {
+                quote!(out.push(::nock::ToNoun::to_noun(#v));)
+            }
+        });
+        quote!(#(#writes)*)
+    }
+
+    /// Statements that read the fields from `rest`, with the last field
+    /// taking the rest if `last` is true.
+    ///
+    /// Flattened fields can be empty, so the last field is the one after
+    /// which only empty fields are left.
+    fn read(&self) -> Tokens {
+        let reads = self.vars.iter().zip(&self.flatten).enumerate().map(
+            |(i, (v, &flat))| {
+                let after = i + 1;
+                let last = if after == self.vars.len() {
+                    quote!(last)
+                } else if self.flatten[after..].iter().all(|&f| f) {
+                    let count = self.count(after);
+                    quote!(last && #count == 0)
+                } else {
+                    quote!(false)
+                };
+                let f = if flat { quote!(flat) } else { quote!(field) };
+                quote!(let #v = ::nock::fields::#f(&mut rest, #last)?;)
+            });
+        quote!(#(#reads)*)
+    }
+}
+
+/// Tag of an enum variant.
+fn tag(ident: &Ident, opts: &Options) -> String {
+    if let Some(tag) = &opts.tag {
+        return tag.clone();
+    }
+    let mut ret = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            ret.push('-');
+        }
+        ret.extend(c.to_lowercase());
+    }
+    ret
+}
+
+/// Add a bound on the trait to every type parameter, and on the fields
+/// trait to the flattened fields that use type parameters.
+fn bounded(input: &DeriveInput,
+           bound: Path,
+           flat_bound: Path)
+           -> syn::Result<syn::Generics> {
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+
+    let params: Vec<Ident> = input.generics
+                                  .type_params()
+                                  .map(|p| p.ident.clone())
+                                  .collect();
+    let fields: Vec<&Fields> = match &input.data {
+        Data::Struct(data) => vec![&data.fields],
+        Data::Enum(data) => data.variants.iter().map(|v| &v.fields).collect(),
+        Data::Union(_) => Vec::new(),
+    };
+    for fields in fields {
+        let binding = Binding::new(quote!(), fields)?;
+        for ty in binding.flat_types() {
+            if uses_params(quote!(#ty), &params) {
+                generics.make_where_clause()
+                        .predicates
+                        .push(parse_quote!(#ty: #flat_bound));
+            }
+        }
+    }
+    Ok(generics)
+}
+
+/// Whether tokens mention any of the type parameters.
+fn uses_params(tokens: Tokens, params: &[Ident]) -> bool {
+    tokens.into_iter().any(|t| match t {
+        TokenTree::Ident(ident) => params.contains(&ident),
+        TokenTree::Group(group) => uses_params(group.stream(), params),
+        _ => false,
+    })
+}
+
+fn to_noun(input: &DeriveInput) -> syn::Result<Tokens> {
+    let name = &input.ident;
+    let generics = bounded(input,
+                           parse_quote!(::nock::ToNoun),
+                           parse_quote!(::nock::fields::ToFields))?;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    match &input.data {
+        Data::Struct(data) => {
+            let binding = Binding::new(quote!(#name), &data.fields)?;
+            let pattern = &binding.pattern;
+            let write = binding.write();
+            Ok(quote! {
+                impl #impl_generics ::nock::fields::ToFields
+                    for #name #ty_generics #where_clause
+                {
+                    #[allow(unused_variables)]
+                    fn to_fields(&self, out: &mut Vec<::nock::Noun>) {
+                        let #pattern = self;
+                        #write
+                    }
+                }
+
+                impl #impl_generics ::nock::ToNoun
+                    for #name #ty_generics #where_clause
+                {
+                    fn to_noun(&self) -> ::nock::Noun {
+                        let mut out = Vec::new();
+                        ::nock::fields::ToFields::to_fields(self, &mut out);
+                        ::nock::fields::cells(out)
+                    }
+                }
+            })
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                let tag = tag(ident, &Options::parse(&variant.attrs)?);
+                let binding = Binding::new(quote!(#name::#ident),
+                                           &variant.fields)?;
+                let pattern = &binding.pattern;
+                let write = binding.write();
+                arms.push(if variant.fields.is_empty() {
+                    quote!(#pattern => ::nock::ToNoun::to_noun(#tag),)
+                } else {
+                    quote! {
+                        #pattern => {
+                            let mut out = vec![::nock::ToNoun::to_noun(#tag)];
+                            {
+                                let out = &mut out;
+                                #write
+                            }
+                            ::nock::fields::cells(out)
+                        }
+                    }
+                });
+            }
+            Ok(quote! {
+                impl #impl_generics ::nock::ToNoun
+                    for #name #ty_generics #where_clause
+                {
+                    fn to_noun(&self) -> ::nock::Noun {
+                        match self {
+                            #(#arms)*
+                        }
+                    }
+                }
+            })
+        }
+        Data::Union(_) => {
+            Err(syn::Error::new_spanned(input, "unions can't be nouns"))
+        }
+    }
+}
+
+fn from_noun(input: &DeriveInput) -> syn::Result<Tokens> {
+    let name = &input.ident;
+    let generics = bounded(input,
+                           parse_quote!(::nock::FromNoun),
+                           parse_quote!(::nock::fields::FromFields))?;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    match &input.data {
+        Data::Struct(data) => {
+            let binding = Binding::new(quote!(#name), &data.fields)?;
+            let pattern = &binding.pattern;
+            let read = binding.read();
+            let count = binding.count(0);
+            // A struct without fields is `~` when it's the whole noun.
+            let check = if binding.flatten.iter().all(|&f| f) {
+                quote! {
+                    if last && Self::FIELDS == 0 &&
+                       *noun != ::nock::Noun::from(0u32) {
+                        return Err(::nock::NockError::conversion::<Self>(noun));
+                    }
+                }
+            } else {
+                quote!()
+            };
+            Ok(quote! {
+                impl #impl_generics ::nock::fields::FromFields
+                    for #name #ty_generics #where_clause
+                {
+                    const FIELDS: usize = #count;
+
+                    #[allow(unused_mut)]
+                    fn from_fields(noun: &::nock::Noun, last: bool)
+                        -> Result<(Self, Option<&::nock::Noun>),
+                                  ::nock::NockError>
+                    {
+                        #check
+                        let mut rest = Some(noun);
+                        #read
+                        Ok((#pattern, rest))
+                    }
+                }
+
+                impl #impl_generics ::nock::FromNoun
+                    for #name #ty_generics #where_clause
+                {
+                    fn from_noun(noun: &::nock::Noun)
+                        -> Result<Self, ::nock::NockError>
+                    {
+                        ::nock::fields::FromFields::from_fields(noun, true)
+                            .map(|x| x.0)
+                    }
+                }
+            })
+        }
+        Data::Enum(data) => {
+            let mut atoms = Vec::new();
+            let mut cells = Vec::new();
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                let tag = tag(ident, &Options::parse(&variant.attrs)?);
+                let binding = Binding::new(quote!(#name::#ident),
+                                           &variant.fields)?;
+                let pattern = &binding.pattern;
+                let read = binding.read();
+                if variant.fields.is_empty() {
+                    atoms.push(quote! {
+                        if *noun == ::nock::ToNoun::to_noun(#tag) {
+                            return Ok(#pattern);
+                        }
+                    });
+                } else {
+                    // Fields that are all flattened can be empty, and then
+                    // the variant is just its tag.
+                    let count = binding.count(0);
+                    if binding.flatten.iter().all(|&f| f) {
+                        atoms.push(quote! {
+                            if #count == 0 &&
+                               *noun == ::nock::ToNoun::to_noun(#tag) {
+                                let last = true;
+                                let mut rest = Some(noun);
+                                #read
+                                return Ok(#pattern);
+                            }
+                        });
+                    }
+                    cells.push(quote! {
+                        if #count != 0 &&
+                           *head == ::nock::ToNoun::to_noun(#tag) {
+                            let last = true;
+                            let mut rest = Some(tail);
+                            #read
+                            return Ok(#pattern);
+                        }
+                    });
+                }
+            }
+            Ok(quote! {
+                impl #impl_generics ::nock::FromNoun
+                    for #name #ty_generics #where_clause
+                {
+                    fn from_noun(noun: &::nock::Noun)
+                        -> Result<Self, ::nock::NockError>
+                    {
+                        match noun.get() {
+                            ::nock::Shape::Atom(_) => { #(#atoms)* }
+                            #[allow(unused_variables)]
+                            ::nock::Shape::Cell(head, tail) => { #(#cells)* }
+                        }
+                        Err(::nock::NockError::conversion::<Self>(noun))
+                    }
+                }
+            })
+        }
+        Data::Union(_) => {
+            Err(syn::Error::new_spanned(input, "unions can't be nouns"))
+        }
+    }
+}
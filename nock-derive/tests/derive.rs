@@ -0,0 +1,160 @@
+use nock::{FromNoun, Noun, ToNoun};
+use nock_derive::{FromNoun, ToNoun};
+
+fn noun(s: &str) -> Noun {
+    s.parse().unwrap()
+}
+
+fn cord(s: &str) -> Noun {
+    s.to_noun()
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct Point {
+    x: u32,
+    y: u32,
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct Triple(u32, u32, u32);
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct Wrapper(Point);
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct Nothing;
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct Pair<T> {
+    a: T,
+    b: T,
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct Nested {
+    id: u32,
+    point: Point,
+    z: u32,
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct Flat {
+    id: u32,
+    #[noun(flatten)]
+    point: Point,
+    z: u32,
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct FlatLast {
+    id: u32,
+    #[noun(flatten)]
+    point: Point,
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct FlatEmpty {
+    id: u32,
+    #[noun(flatten)]
+    nothing: Nothing,
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct FlatEmptyInside {
+    id: u32,
+    #[noun(flatten)]
+    nothing: Nothing,
+    z: u32,
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct OnlyEmpty(#[noun(flatten)] Nothing, #[noun(flatten)] Nothing);
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+struct FlatGeneric<T> {
+    id: u32,
+    #[noun(flatten)]
+    inner: T,
+}
+
+#[derive(ToNoun, FromNoun, PartialEq, Debug)]
+enum Shape {
+    Empty,
+    Circle { radius: u32 },
+    LineSegment(Point, Point),
+    #[noun(tag = "box")]
+    Rectangle(u32, u32),
+    Moved(#[noun(flatten)] Point, u32),
+    Blank(#[noun(flatten)] Nothing),
+}
+
+fn round_trip<T: ToNoun + FromNoun + PartialEq + std::fmt::Debug>(x: T,
+                                                                   n: Noun) {
+    assert_eq!(x.to_noun(), n);
+    assert_eq!(T::from_noun(&n), Ok(x));
+}
+
+#[test]
+fn test_struct() {
+    round_trip(Point { x: 1, y: 2 }, noun("[1 2]"));
+    round_trip(Triple(1, 2, 3), noun("[1 2 3]"));
+    round_trip(Wrapper(Point { x: 1, y: 2 }), noun("[1 2]"));
+    round_trip(Nothing, noun("0"));
+    round_trip(Pair { a: 1u8, b: 2u8 }, noun("[1 2]"));
+    round_trip(Pair {
+                   a: Point { x: 1, y: 2 },
+                   b: Point { x: 3, y: 4 },
+               },
+               noun("[[1 2] 3 4]"));
+
+    assert!(Point::from_noun(&noun("1")).is_err());
+    assert!(Point::from_noun(&noun("[[1 2] 3]")).is_err());
+    assert!(Triple::from_noun(&noun("[1 2]")).is_err());
+    assert!(Nothing::from_noun(&noun("1")).is_err());
+}
+
+#[test]
+fn test_flatten() {
+    let point = Point { x: 2, y: 3 };
+    round_trip(Nested { id: 1, point: Point { x: 2, y: 3 }, z: 4 },
+               noun("[1 [2 3] 4]"));
+    round_trip(Flat { id: 1, point: Point { x: 2, y: 3 }, z: 4 },
+               noun("[1 2 3 4]"));
+    round_trip(FlatLast { id: 1, point }, noun("[1 2 3]"));
+    assert!(Flat::from_noun(&noun("[1 2 3]")).is_err());
+
+    // Empty flattened fields leave the rest to the fields around them.
+    round_trip(FlatEmpty { id: 1, nothing: Nothing }, noun("1"));
+    round_trip(FlatEmptyInside { id: 1, nothing: Nothing, z: 2 },
+               noun("[1 2]"));
+    round_trip(OnlyEmpty(Nothing, Nothing), noun("0"));
+    assert!(OnlyEmpty::from_noun(&noun("1")).is_err());
+
+    round_trip(FlatGeneric { id: 1, inner: Point { x: 2, y: 3 } },
+               noun("[1 2 3]"));
+    round_trip(FlatGeneric { id: 1, inner: Nothing }, noun("1"));
+    round_trip(FlatGeneric {
+                   id: 1,
+                   inner: FlatGeneric { id: 2, inner: Point { x: 2, y: 3 } },
+               },
+               noun("[1 2 2 3]"));
+}
+
+#[test]
+fn test_enum() {
+    let point = |x, y| Point { x, y };
+    round_trip(Shape::Empty, cord("empty"));
+    round_trip(Shape::Circle { radius: 5 },
+               Noun::cell(cord("circle"), noun("5")));
+    round_trip(Shape::LineSegment(point(1, 2), point(3, 4)),
+               Noun::cell(cord("line-segment"), noun("[[1 2] 3 4]")));
+    round_trip(Shape::Rectangle(3, 4), Noun::cell(cord("box"), noun("[3 4]")));
+    round_trip(Shape::Moved(point(1, 2), 3),
+               Noun::cell(cord("moved"), noun("[1 2 3]")));
+    round_trip(Shape::Blank(Nothing), cord("blank"));
+
+    assert!(Shape::from_noun(&cord("circle")).is_err());
+    assert!(Shape::from_noun(&cord("rectangle")).is_err());
+    assert!(Shape::from_noun(&Noun::cell(cord("empty"), noun("0"))).is_err());
+    assert!(Shape::from_noun(&Noun::cell(cord("box"), noun("3"))).is_err());
+}
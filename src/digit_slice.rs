@@ -37,23 +37,8 @@ impl FromDigits for BigUint {
     type Err = ();
 
     fn from_digits(digits: &[u8]) -> Result<Self, Self::Err> {
-        let mut v = digits.to_vec();
-        while (v.len() % 4) != 0 {
-            v.push(0);
-        }
-
-        unsafe {
-            let ptr = v.as_mut_ptr() as *mut BigDigit;
-            let len = v.len() / 4;
-            // I hope possibly truncating capacity down by 1 or 3 bytes won't
-            // be a problem.
-            let cap = v.capacity() / 4;
-            // We'll reuse the bits, don't collect the old one.
-            mem::forget(v);
-
-            let biguint_vec: Vec<BigDigit> = Vec::from_raw_parts(ptr, len, cap);
-            Ok(mem::transmute(biguint_vec))
-        }
+        // Reusing the bytes as the digit vector would misalign it.
+        Ok(BigUint::from_bytes_le(digits))
     }
 }
 
@@ -92,6 +77,7 @@ primitive_impl!(u8);
 primitive_impl!(u16);
 primitive_impl!(u32);
 primitive_impl!(u64);
+primitive_impl!(u128);
 primitive_impl!(usize);
 
 /// Return the bit position of the most significant bit.
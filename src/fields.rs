@@ -0,0 +1,111 @@
+//! Nouns made of a sequence of fields.
+//!
+//! A struct `{a, b, c}` is the right-nested cell `[a b c]`. When one of the
+//! fields is itself such a struct, its fields can be flattened into the
+//! sequence, so `{a, {b, c}, d}` becomes `[a b c d]` instead of
+//! `[a [b c] d]`. This is what `#[derive(ToNoun, FromNoun)]` of the
+//! `nock-derive` crate uses for structs and enum variants.
+
+use {FromNoun, NockError, Noun, Shape};
+
+/// Types that can write themselves as a sequence of fields.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be flattened into a sequence of fields",
+    note = "only structs that derive `ToNoun` can be flattened")]
+pub trait ToFields {
+    /// Append the fields to a sequence.
+    fn to_fields(&self, out: &mut Vec<Noun>);
+}
+
+/// Types that can be read from a sequence of fields.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be flattened into a sequence of fields",
+    note = "only structs that derive `FromNoun` can be flattened")]
+pub trait FromFields: Sized {
+    /// Number of fields, counting those of flattened values.
+    const FIELDS: usize;
+
+    /// Read the fields from the front of a right-nested cell.
+    ///
+    /// Return the value and the rest of the noun after the fields. If
+    /// `last` is set, the last field takes the rest of the noun, and no
+    /// rest is returned.
+    fn from_fields(noun: &Noun,
+                   last: bool)
+                   -> Result<(Self, Option<&Noun>), NockError>;
+}
+
+/// Build a right-nested cell from fields, or `~` if there are none.
+pub fn cells(fields: Vec<Noun>) -> Noun {
+    if fields.is_empty() {
+        Noun::from(0u32)
+    } else {
+        fields.into_iter().collect()
+    }
+}
+
+/// Read the next field of a sequence.
+///
+/// `rest` is the rest of the noun, and is advanced past the field. If
+/// `last` is set, the field takes the whole rest.
+pub fn field<T: FromNoun>(rest: &mut Option<&Noun>,
+                          last: bool)
+                          -> Result<T, NockError> {
+    let noun = rest.ok_or_else(missing)?;
+    if last {
+        *rest = None;
+        return T::from_noun(noun);
+    }
+    match noun.get() {
+        Shape::Cell(a, b) => {
+            *rest = Some(b);
+            T::from_noun(a)
+        }
+        Shape::Atom(_) => Err(NockError::conversion::<T>(noun)),
+    }
+}
+
+/// Read the flattened fields of a value from a sequence.
+pub fn flat<T: FromFields>(rest: &mut Option<&Noun>,
+                           last: bool)
+                           -> Result<T, NockError> {
+    if T::FIELDS == 0 {
+        // Nothing to read, and the rest is left to the next fields.
+        return T::from_fields(&Noun::from(0u32), false).map(|x| x.0);
+    }
+    let noun = rest.ok_or_else(missing)?;
+    let (ret, next) = T::from_fields(noun, last)?;
+    *rest = next;
+    Ok(ret)
+}
+
+fn missing() -> NockError {
+    NockError::Other("Missing field".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use Noun;
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        let noun = cells(vec![Noun::from(1u32),
+                              Noun::from(2u32),
+                              Noun::from(3u32)]);
+        assert_eq!(noun, "[1 2 3]".parse().unwrap());
+        assert_eq!(cells(Vec::new()), Noun::from(0u32));
+
+        let mut rest = Some(&noun);
+        assert_eq!(field::<u32>(&mut rest, false), Ok(1));
+        assert_eq!(rest, Some(&"[2 3]".parse().unwrap()));
+        assert_eq!(field::<(u32, u32)>(&mut rest, true), Ok((2, 3)));
+        assert_eq!(rest, None);
+        assert!(field::<Noun>(&mut rest, true).is_err());
+
+        let mut rest = Some(&noun);
+        assert_eq!(field::<u32>(&mut rest, false), Ok(1));
+        assert_eq!(field::<u32>(&mut rest, false), Ok(2));
+        assert!(field::<u32>(&mut rest, false).is_err());
+    }
+}
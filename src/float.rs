@@ -0,0 +1,155 @@
+//! Floating-point numbers as IEEE 754 bit pattern atoms.
+//!
+//! Hoon keeps floats as atoms of their bits, `@rh`, `@rs`, `@rd` and `@rq`
+//! for half, single, double and quad precision. Signed zeros and infinities
+//! keep their bits, but all NaNs are converted to the single NaN that Hoon
+//! float arithmetic produces.
+
+use {FromNoun, NockError, Noun, ToNoun};
+
+/// Half-precision float, `@rh`, given by its bits.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Half(pub u16);
+
+/// Quad-precision float, `@rq`, given by its bits.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Quad(pub u128);
+
+impl Half {
+    /// Hoon's NaN.
+    pub const NAN: Half = Half(0x7e00);
+
+    pub fn is_nan(self) -> bool {
+        self.0 & 0x7fff > 0x7c00
+    }
+}
+
+impl Quad {
+    /// Hoon's NaN.
+    pub const NAN: Quad = Quad(0x7fff_8000 << 96);
+
+    pub fn is_nan(self) -> bool {
+        self.0 & !(1 << 127) > 0x7fff << 112
+    }
+}
+
+impl ToNoun for f32 {
+    fn to_noun(&self) -> Noun {
+        let bits = if self.is_nan() { 0x7fc0_0000 } else { self.to_bits() };
+        Noun::from(bits)
+    }
+}
+
+impl FromNoun for f32 {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        let bits = u32::from_noun(n)
+                       .map_err(|_| NockError::conversion::<f32>(n))?;
+        Ok(f32::from_bits(bits))
+    }
+}
+
+impl ToNoun for f64 {
+    fn to_noun(&self) -> Noun {
+        let bits = if self.is_nan() {
+            0x7ff8_0000_0000_0000
+        } else {
+            self.to_bits()
+        };
+        Noun::from(bits)
+    }
+}
+
+impl FromNoun for f64 {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        let bits = u64::from_noun(n)
+                       .map_err(|_| NockError::conversion::<f64>(n))?;
+        Ok(f64::from_bits(bits))
+    }
+}
+
+impl ToNoun for Half {
+    fn to_noun(&self) -> Noun {
+        let bits = if self.is_nan() { Half::NAN.0 } else { self.0 };
+        Noun::from(bits)
+    }
+}
+
+impl FromNoun for Half {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        u16::from_noun(n)
+            .map(Half)
+            .map_err(|_| NockError::conversion::<Half>(n))
+    }
+}
+
+impl ToNoun for Quad {
+    fn to_noun(&self) -> Noun {
+        let bits = if self.is_nan() { Quad::NAN.0 } else { self.0 };
+        Noun::from(bits)
+    }
+}
+
+impl FromNoun for Quad {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        u128::from_noun(n)
+            .map(Quad)
+            .map_err(|_| NockError::conversion::<Quad>(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64;
+    use {FromNoun, Noun};
+    use super::{Half, Quad};
+
+    #[test]
+    fn test_float() {
+        // .1, .-1 and .~1
+        assert_eq!(Noun::from(1.0f32), Noun::from(0x3f80_0000u32));
+        assert_eq!(Noun::from(-1.0f32), Noun::from(0xbf80_0000u32));
+        assert_eq!(Noun::from(1.0f64), Noun::from(0x3ff0_0000_0000_0000u64));
+        assert_eq!(f32::from_noun(&Noun::from(0x3fc0_0000u32)), Ok(1.5));
+        assert_eq!(f64::from_noun(&Noun::from(0x4004_0000_0000_0000u64)),
+                   Ok(2.5));
+
+        // Signed zeros and infinities keep their sign.
+        assert_eq!(Noun::from(-0.0f32), Noun::from(0x8000_0000u32));
+        assert_eq!(Noun::from(0.0f64), Noun::from(0u32));
+        assert_eq!(Noun::from(f64::NEG_INFINITY),
+                   Noun::from(0xfff0_0000_0000_0000u64));
+        let zero = f64::from_noun(&Noun::from(0x8000_0000_0000_0000u64));
+        assert!(zero.unwrap().is_sign_negative());
+
+        // NaNs are canonical.
+        assert_eq!(Noun::from(f32::from_bits(0xffc0_0001)),
+                   Noun::from(0x7fc0_0000u32));
+        assert_eq!(Noun::from(-f64::NAN),
+                   Noun::from(0x7ff8_0000_0000_0000u64));
+        assert!(f32::from_noun(&Noun::from(0x7fc0_0000u32)).unwrap().is_nan());
+
+        assert!(f32::from_noun(&Noun::from(1u64 << 32)).is_err());
+        assert!(f64::from_noun(&"[1 2]".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_half_quad() {
+        // .~~1 and .~~~1
+        assert_eq!(Noun::from(Half(0x3c00)), Noun::from(0x3c00u32));
+        assert_eq!(Half::from_noun(&Noun::from(0xbc00u32)), Ok(Half(0xbc00)));
+        let one = 0x3fffu128 << 112;
+        assert_eq!(Noun::from(Quad(one)), Noun::from(one));
+        assert_eq!(Quad::from_noun(&Noun::from(one)), Ok(Quad(one)));
+
+        assert!(!Half(0x7c00).is_nan());
+        assert!(Half(0xfc01).is_nan());
+        assert_eq!(Noun::from(Half(0xfc01)), Noun::from(0x7e00u32));
+        assert_eq!(Noun::from(Half(0x8000)), Noun::from(0x8000u32));
+        assert!(!Quad(0x7fff << 112).is_nan());
+        assert!(Quad((0xffff << 112) | 1).is_nan());
+        assert_eq!(Noun::from(Quad((0xffff << 112) | 1)),
+                   Noun::from(Quad::NAN.0));
+
+        assert!(Half::from_noun(&Noun::from(0x1_0000u32)).is_err());
+    }
+}
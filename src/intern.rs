@@ -0,0 +1,364 @@
+//! Hash-consing of nouns.
+
+use std::collections::{HashMap, HashSet};
+use std::hash;
+use std::mem;
+use fnv;
+use {Shape, Noun, Inner, Rc};
+
+type Fnv = hash::BuildHasherDefault<fnv::FnvHasher>;
+
+impl Noun {
+    /// Return whether two nouns are the same object in memory.
+    ///
+    /// Nouns that are the same object are always equal, and this is much
+    /// faster to check than equality. Inline atoms have no memory of their
+    /// own, and are compared by value.
+    pub fn ptr_eq(a: &Noun, b: &Noun) -> bool {
+        match (&a.value, &b.value) {
+            (&Inner::Direct(x), &Inner::Direct(y)) => x == y,
+            (Inner::Atom(x), Inner::Atom(y)) => Rc::ptr_eq(x, y),
+            (Inner::Cell(a1, b1), Inner::Cell(a2, b2)) => {
+                Rc::ptr_eq(a1, a2) && Rc::ptr_eq(b1, b2)
+            }
+            _ => false,
+        }
+    }
+
+    /// Compare two nouns for equality, and make equal subnouns share
+    /// memory.
+    ///
+    /// If the nouns are equal, `other` is replaced by a copy of `self`.
+    /// Otherwise every subnoun of `other` that is equal to the subnoun of
+    /// `self` at the same axis is replaced by it. Subnouns that `other`
+    /// shares with other nouns are left alone, so nouns other than `other`
+    /// never change.
+    ///
+    /// This means unifying can't merge structure that is already shared,
+    /// and only pays off when `other` is kept afterwards, such as a freshly
+    /// decoded noun that replaces an older equal one. Use an `Interner` to
+    /// dedupe nouns that are held in many places.
+    pub fn unify(&self, other: &mut Noun) -> bool {
+        enum Task<'a> {
+            /// Compare a subnoun of `self` with one taken out of `other`.
+            Visit(&'a Noun, Noun),
+            /// Put back the children of a cell of `other` once they have
+            /// been compared.
+            Join(&'a Noun, Noun),
+            /// A shared child of `other` that was compared in place.
+            Shared(bool),
+        }
+
+        // Results of the comparisons, with the subnoun of `other` if it was
+        // taken out of its cell.
+        let mut results: Vec<(bool, Option<Noun>)> = Vec::new();
+        let mut tasks = vec![Task::Visit(self,
+                                         mem::replace(other,
+                                                      Noun::from(0u32)))];
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(a, mut b) => {
+                    if Noun::ptr_eq(a, &b) {
+                        results.push((true, Some(b)));
+                        continue;
+                    }
+                    let mut children = Vec::new();
+                    match (&a.value, &mut b.value) {
+                        (Inner::Cell(p1, q1), Inner::Cell(p2, q2)) => {
+                            for (x, y) in [(p1, p2), (q1, q2)] {
+                                children.push(match Rc::get_mut(y) {
+                                    Some(y) => {
+                                        let y = mem::replace(y,
+                                                             Noun::from(0u32));
+                                        Task::Visit(x, y)
+                                    }
+                                    None => Task::Shared(x == y),
+                                });
+                            }
+                        }
+                        _ => {
+                            let same = *a == b;
+                            results.push((same, Some(b)));
+                            continue;
+                        }
+                    }
+                    tasks.push(Task::Join(a, b));
+                    // Visit the head first.
+                    tasks.extend(children.into_iter().rev());
+                }
+                Task::Join(a, mut b) => {
+                    let q = results.pop().unwrap();
+                    let p = results.pop().unwrap();
+                    let same = p.0 && q.0;
+                    if let (Inner::Cell(p1, q1), Inner::Cell(p2, q2)) =
+                           (&a.value, &mut b.value) {
+                        for (x, y, (same, taken)) in
+                            [(p1, p2, p), (q1, q2, q)] {
+                            if same {
+                                *y = x.clone();
+                            } else if let Some(taken) = taken {
+                                *Rc::get_mut(y).unwrap() = taken;
+                            }
+                        }
+                    }
+                    results.push((same, Some(b)));
+                }
+                Task::Shared(same) => results.push((same, None)),
+            }
+        }
+
+        match results.pop() {
+            Some((true, _)) => {
+                *other = self.clone();
+                true
+            }
+            Some((false, Some(b))) => {
+                *other = b;
+                false
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Noun whose children are canonical, so that it's equal to another such
+/// noun exactly when their children are the same objects.
+struct Canonical(Noun);
+
+impl PartialEq for Canonical {
+    fn eq(&self, other: &Canonical) -> bool {
+        match (self.0.get(), other.0.get()) {
+            (Shape::Atom(x), Shape::Atom(y)) => x == y,
+            (Shape::Cell(a1, b1), Shape::Cell(a2, b2)) => {
+                Noun::ptr_eq(a1, a2) && Noun::ptr_eq(b1, b2)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Canonical {}
+
+impl hash::Hash for Canonical {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+/// Store of canonical nouns.
+///
+/// Interning a noun returns a noun that shares its memory with every other
+/// equal noun interned in the same store, so repeated structure is stored
+/// only once, and interned nouns can be compared with `Noun::ptr_eq`.
+///
+/// The store keeps every noun interned in it alive until it is cleared.
+#[derive(Default)]
+pub struct Interner {
+    nouns: HashSet<Canonical, Fnv>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Default::default()
+    }
+
+    /// Return the canonical version of a noun.
+    pub fn intern(&mut self, noun: &Noun) -> Noun {
+        // Canonical versions of the subnouns, by address, so that shared
+        // subnouns are only visited once.
+        let mut done: HashMap<usize, Noun, Fnv> = HashMap::default();
+        let mut stack = vec![(noun, false)];
+
+        while let Some((n, expanded)) = stack.pop() {
+            if done.contains_key(&n.addr()) {
+                continue;
+            }
+            let canonical = match n.get() {
+                Shape::Atom(_) => self.canonical(n.clone()),
+                Shape::Cell(a, b) => {
+                    if !expanded {
+                        stack.push((n, true));
+                        stack.push((b, false));
+                        stack.push((a, false));
+                        continue;
+                    }
+                    let cell = Noun::cell(done[&a.addr()].clone(),
+                                          done[&b.addr()].clone());
+                    self.canonical(cell)
+                }
+            };
+            done.insert(n.addr(), canonical);
+        }
+
+        done[&noun.addr()].clone()
+    }
+
+    /// Look up or store a noun whose children are already canonical.
+    fn canonical(&mut self, noun: Noun) -> Noun {
+        if let Inner::Direct(_) = noun.value {
+            return noun;
+        }
+        let key = Canonical(noun);
+        if let Some(x) = self.nouns.get(&key) {
+            return x.0.clone();
+        }
+        let ret = key.0.clone();
+        self.nouns.insert(key);
+        ret
+    }
+
+    /// Number of distinct nouns stored, not counting small atoms.
+    pub fn len(&self) -> usize {
+        self.nouns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nouns.is_empty()
+    }
+
+    /// Forget all the stored nouns.
+    pub fn clear(&mut self) {
+        self.nouns.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Noun, Shape};
+    use super::Interner;
+
+    #[test]
+    fn test_ptr_eq() {
+        let a: Noun = "[1 2 18446744073709551616]".parse().unwrap();
+        let b: Noun = "[1 2 18446744073709551616]".parse().unwrap();
+        assert!(Noun::ptr_eq(&a, &a.clone()));
+        assert!(!Noun::ptr_eq(&a, &b));
+        assert!(Noun::ptr_eq(&Noun::from(1u32), &Noun::from(1u32)));
+        assert!(!Noun::ptr_eq(&Noun::from(1u32), &Noun::from(2u32)));
+    }
+
+    #[test]
+    fn test_unify() {
+        let a: Noun = "[[1 2] 18446744073709551616 3]".parse().unwrap();
+        let mut b: Noun = "[[1 2] 18446744073709551616 3]".parse().unwrap();
+        assert!(a.unify(&mut b));
+        assert!(Noun::ptr_eq(&a, &b));
+
+        // Equal subnouns before the difference are shared.
+        let mut c: Noun = "[[1 2] 18446744073709551616 4]".parse().unwrap();
+        assert!(!a.unify(&mut c));
+        assert_eq!(c, "[[1 2] 18446744073709551616 4]".parse().unwrap());
+        match (a.get_122(), c.get_122()) {
+            (Some((x1, y1, _)), Some((x2, y2, _))) => {
+                assert!(Noun::ptr_eq(x1, x2));
+                assert!(Noun::ptr_eq(y1, y2));
+            }
+            _ => panic!("Bad shape"),
+        }
+
+        // Shared subnouns of the other noun are left alone.
+        let d: Noun = "[18446744073709551616 5]".parse().unwrap();
+        let mut e = Noun::cell(Noun::from(1u32), d.clone());
+        let f: Noun = "[1 18446744073709551616 6]".parse().unwrap();
+        assert!(!f.unify(&mut e));
+        match (d.get(), e.get_122(), f.get_122()) {
+            (Shape::Cell(x, _), Some((_, y, _)), Some((_, z, _))) => {
+                assert!(Noun::ptr_eq(y, z));
+                assert!(!Noun::ptr_eq(x, z));
+            }
+            _ => panic!("Bad shape"),
+        }
+        assert_eq!(e, "[1 18446744073709551616 5]".parse().unwrap());
+
+        // Equal subnouns on both sides of a difference are shared.
+        let g: Noun = "[[18446744073709551616 2] 18446744073709551616 3]"
+                          .parse()
+                          .unwrap();
+        let mut h: Noun = "[[18446744073709551616 4] 18446744073709551616 3]"
+                              .parse()
+                              .unwrap();
+        assert!(!g.unify(&mut h));
+        match (g.get(), h.get()) {
+            (Shape::Cell(x1, y1), Shape::Cell(x2, y2)) => {
+                assert!(Noun::ptr_eq(y1, y2));
+                match (x1.get(), x2.get()) {
+                    (Shape::Cell(z1, _), Shape::Cell(z2, _)) => {
+                        assert!(Noun::ptr_eq(z1, z2))
+                    }
+                    _ => panic!("Bad shape"),
+                }
+            }
+            _ => panic!("Bad shape"),
+        }
+
+        // The sharing outlives the noun unified with.
+        let kept = {
+            let old: Noun = "[18446744073709551616 [1 2] 3]".parse().unwrap();
+            let mut new: Noun = "[18446744073709551616 [1 2] 4]"
+                                    .parse()
+                                    .unwrap();
+            assert!(!old.unify(&mut new));
+            let (x, _, _) = old.get_122().unwrap();
+            (x.clone(), new)
+        };
+        let (x, new) = kept;
+        let (y, z, _) = new.get_122().unwrap();
+        assert!(Noun::ptr_eq(&x, y));
+        assert_eq!(*z, "[1 2]".parse().unwrap());
+
+        // Deep nouns don't overflow the stack.
+        let list = |end: u32| {
+            let mut ret = Noun::from(end);
+            for i in 0..100_000u64 {
+                ret = Noun::cell(Noun::from(u64::MAX as u128 + i as u128),
+                                 ret);
+            }
+            ret
+        };
+        let (a, mut b, mut c) = (list(0), list(0), list(1));
+        assert!(a.unify(&mut b));
+        assert!(Noun::ptr_eq(&a, &b));
+        assert!(!a.unify(&mut c));
+        assert_eq!(c, list(1));
+        if let (Shape::Cell(x, _), Shape::Cell(y, _)) = (a.get(), c.get()) {
+            assert!(Noun::ptr_eq(x, y));
+        }
+    }
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let a: Noun = "[[1 2] 18446744073709551616 [1 2]]".parse().unwrap();
+        let b: Noun = "[[1 2] 18446744073709551616 [1 2]]".parse().unwrap();
+
+        let a = interner.intern(&a);
+        let b = interner.intern(&b);
+        assert_eq!(a, b);
+        assert!(Noun::ptr_eq(&a, &b));
+        // [1 2], the big atom, [big [1 2]] and the whole noun.
+        assert_eq!(interner.len(), 4);
+
+        // Equal subnouns share memory.
+        if let Some((x, _, y)) = a.get_122() {
+            assert!(Noun::ptr_eq(x, y));
+        } else {
+            panic!("Bad shape");
+        }
+
+        let c = interner.intern(&"[[1 2] 3]".parse().unwrap());
+        if let (Shape::Cell(x, _), Shape::Cell(y, _)) = (a.get(), c.get()) {
+            assert!(Noun::ptr_eq(x, y));
+        }
+        assert_eq!(interner.len(), 5);
+
+        // Deep nouns don't overflow the stack.
+        let mut deep = Noun::from(0u32);
+        for i in 0..1_000u32 {
+            deep = Noun::cell(Noun::from(i), deep);
+        }
+        let x = interner.intern(&deep);
+        assert!(Noun::ptr_eq(&x, &interner.intern(&deep)));
+        assert_eq!(interner.len(), 1_005);
+    }
+}
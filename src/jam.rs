@@ -0,0 +1,335 @@
+//! Bit-packed noun serialization compatible with Urbit's `jam` and `cue`.
+
+use std::collections::HashMap;
+use std::hash;
+use fnv;
+use digit_slice::msb;
+use {Shape, Noun, NockError, NockResult};
+
+impl Noun {
+    /// Serialize the noun into a single atom.
+    ///
+    /// Repeated subnouns are written as backreferences to their first
+    /// occurrence, so nouns with a lot of internal sharing stay small.
+    pub fn jam(&self) -> Noun {
+        let fnv = hash::BuildHasherDefault::<fnv::FnvHasher>::default();
+        // Subnouns by memory address, these are found without having to
+        // compare values.
+        let mut addrs: HashMap<usize, usize, _> = HashMap::with_hasher(fnv);
+        // Subnouns by value, these catch equal nouns in separate allocations.
+        let mut values: HashMap<&Noun, usize> = HashMap::new();
+        let mut out = BitWriter::new();
+        let mut stack = vec![self];
+
+        while let Some(noun) = stack.pop() {
+            let seen = addrs.get(&noun.addr())
+                            .or_else(|| values.get(noun))
+                            .cloned();
+            if let Some(pos) = seen {
+                match noun.get() {
+                    // Small atoms are cheaper to repeat than to refer to.
+                    Shape::Atom(x) if msb(x) <= bit_len(pos) => {
+                        out.push(false);
+                        out.mat(x);
+                    }
+                    _ => {
+                        out.push(true);
+                        out.push(true);
+                        out.mat_usize(pos);
+                    }
+                }
+                continue;
+            }
+
+            addrs.insert(noun.addr(), out.len);
+            values.insert(noun, out.len);
+            match noun.get() {
+                Shape::Atom(x) => {
+                    out.push(false);
+                    out.mat(x);
+                }
+                Shape::Cell(a, b) => {
+                    out.push(true);
+                    out.push(false);
+                    stack.push(b);
+                    stack.push(a);
+                }
+            }
+        }
+
+        Noun::atom(out.digits())
+    }
+
+    /// Deserialize a noun from an atom produced by `jam`.
+    pub fn cue(&self) -> NockResult {
+        let data = match self.get() {
+            Shape::Atom(x) => x,
+            _ => return Err(NockError::conversion::<Noun>(self)),
+        };
+        let input = BitReader {
+            data,
+            len: msb(data),
+        };
+
+        let mut refs: HashMap<usize, Noun> = HashMap::new();
+        // Cells whose head or tail is still being decoded.
+        let mut stack: Vec<(usize, Option<Noun>)> = Vec::new();
+        let mut cursor = 0;
+
+        loop {
+            let pos = cursor;
+            let mut noun = if !input.bit(cursor) {
+                let (len, digits) = try_cue(self, input.rub(cursor + 1))?;
+                cursor += 1 + len;
+                let atom = Noun::atom(&digits);
+                refs.insert(pos, atom.clone());
+                atom
+            } else if !input.bit(cursor + 1) {
+                cursor += 2;
+                stack.push((pos, None));
+                continue;
+            } else {
+                let (len, digits) = try_cue(self, input.rub(cursor + 2))?;
+                cursor += 2 + len;
+                let target = try_cue(self, to_usize(&digits))?;
+                try_cue(self, refs.get(&target).cloned())?
+            };
+
+            // Assemble any cells this noun completes.
+            loop {
+                match stack.pop() {
+                    None => return Ok(noun),
+                    Some((pos, None)) => {
+                        stack.push((pos, Some(noun)));
+                        break;
+                    }
+                    Some((pos, Some(head))) => {
+                        noun = Noun::cell(head, noun);
+                        refs.insert(pos, noun.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn try_cue<T>(jam: &Noun, x: Option<T>) -> Result<T, NockError> {
+    x.ok_or_else(|| NockError::conversion::<Noun>(jam))
+}
+
+/// Number of bits needed to represent x.
+fn bit_len(x: usize) -> usize {
+    (usize::BITS - x.leading_zeros()) as usize
+}
+
+/// Little-endian bit stream.
+struct BitWriter {
+    data: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            data: Vec::new(),
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.data.push(0);
+        }
+        if bit {
+            self.data[self.len / 8] |= 1 << (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    /// Write the n low bits of a little-endian digit sequence.
+    ///
+    /// The sequence must be at least n bits long.
+    fn push_bits(&mut self, digits: &[u8], n: usize) {
+        for i in 0..n {
+            self.push(digits[i / 8] & (1 << (i % 8)) != 0);
+        }
+    }
+
+    /// Write a self-delimiting length-prefixed atom.
+    fn mat(&mut self, digits: &[u8]) {
+        if digits.is_empty() {
+            self.push(true);
+            return;
+        }
+        let b = msb(digits);
+        let c = bit_len(b);
+        for _ in 0..c {
+            self.push(false);
+        }
+        self.push(true);
+        for i in 0..(c - 1) {
+            self.push(b & (1 << i) != 0);
+        }
+        self.push_bits(digits, b);
+    }
+
+    fn mat_usize(&mut self, mut x: usize) {
+        let mut digits = Vec::new();
+        while x != 0 {
+            digits.push(x as u8);
+            x >>= 8;
+        }
+        self.mat(&digits);
+    }
+
+    fn digits(&self) -> &[u8] {
+        let mut n = self.data.len();
+        while n > 0 && self.data[n - 1] == 0 {
+            n -= 1;
+        }
+        &self.data[..n]
+    }
+}
+
+fn to_usize(digits: &[u8]) -> Option<usize> {
+    if digits.len() > (usize::BITS / 8) as usize {
+        return None;
+    }
+    Some(digits.iter().rev().fold(0, |acc, &d| (acc << 8) | d as usize))
+}
+
+/// Little-endian bit stream reader, reads past the end as zero bits.
+struct BitReader<'a> {
+    data: &'a [u8],
+    len: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn bit(&self, pos: usize) -> bool {
+        pos < self.len && self.data[pos / 8] & (1 << (pos % 8)) != 0
+    }
+
+    /// Read n bits starting at pos into a little-endian digit sequence.
+    fn bits(&self, pos: usize, n: usize) -> Vec<u8> {
+        let mut ret = vec![0u8; n.div_ceil(8)];
+        for i in 0..n {
+            if self.bit(pos + i) {
+                ret[i / 8] |= 1 << (i % 8);
+            }
+        }
+        while ret.last() == Some(&0) {
+            ret.pop();
+        }
+        ret
+    }
+
+    /// Read a length-prefixed atom written by `mat`.
+    ///
+    /// Returns the number of bits consumed and the atom digits.
+    fn rub(&self, pos: usize) -> Option<(usize, Vec<u8>)> {
+        let mut c = 0;
+        while !self.bit(pos + c) {
+            c += 1;
+            if pos + c >= self.len {
+                return None;
+            }
+        }
+        if c == 0 {
+            return Some((1, Vec::new()));
+        }
+
+        if c > usize::BITS as usize {
+            return None;
+        }
+        let d = pos + c + 1;
+        // The length comes from untrusted input, so check it against the
+        // data before allocating anything for the atom.
+        let b = 1usize.checked_shl((c - 1) as u32)?
+                      .checked_add(to_usize(&self.bits(d, c - 1))?)?;
+        let start = d.checked_add(c - 1)?;
+        if b > self.len.saturating_sub(start) {
+            return None;
+        }
+        Some((c + c + b, self.bits(start, b)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+    use {Noun, msb};
+
+    fn bits(noun: &Noun) -> usize {
+        noun.fold(|x| match x {
+            ::Shape::Atom(x) => msb(x),
+            _ => panic!("not an atom"),
+        })
+    }
+
+    fn jam(input: &str) -> Noun {
+        input.parse::<Noun>().unwrap().jam()
+    }
+
+    fn round_trip(noun: &Noun) {
+        assert_eq!(&noun.jam().cue().unwrap(), noun);
+    }
+
+    #[test]
+    fn test_jam() {
+        assert_eq!(jam("0"), Noun::from(2u32));
+        assert_eq!(jam("1"), Noun::from(12u32));
+        assert_eq!(jam("2"), Noun::from(72u32));
+        assert_eq!(jam("19"), Noun::from(2480u32));
+        assert_eq!(jam("[0 0]"), Noun::from(41u32));
+        assert_eq!(jam("[1 2]"), Noun::from(4657u32));
+        // Repeated small atom is written out again.
+        assert_eq!(jam("[1 1]"), Noun::from(817u32));
+    }
+
+    #[test]
+    fn test_cue() {
+        assert_eq!(Noun::from(2u32).cue(), Ok(Noun::from(0u32)));
+        assert_eq!(Noun::from(12u32).cue(), Ok(Noun::from(1u32)));
+        assert_eq!(Noun::from(817u32).cue(), Ok("[1 1]".parse().unwrap()));
+        assert!(Noun::from(0u32).cue().is_err());
+        // Backreference to a position that holds no noun.
+        assert!(Noun::from(0b1_0111u32).cue().is_err());
+        assert!("[1 2]".parse::<Noun>().unwrap().cue().is_err());
+
+        // Atoms with length prefixes that would overflow or exhaust memory.
+        for &(c, len) in &[(64u32, u64::MAX >> 1), (64, 0), (40, 12_345)] {
+            let one = BigUint::from(1u32);
+            let prefix = (one.clone() << (1 + c as usize)) |
+                         (BigUint::from(len) << (2 + c as usize));
+            assert!(Noun::from(prefix.clone()).cue().is_err());
+            // With some data after it.
+            let data = prefix | (one << 300);
+            assert!(Noun::from(data).cue().is_err());
+        }
+
+        round_trip(&"[[1 2] [1 2] 3 [1 2]]".parse().unwrap());
+        round_trip(&"[123.456.789.123.456.789.123 [0 1] 0 1]"
+                        .parse()
+                        .unwrap());
+        round_trip(&(0u32..2000).map(Noun::from).collect());
+    }
+
+    #[test]
+    fn test_jam_sharing() {
+        // Equal subnouns in separate allocations become backreferences.
+        let big = "[123.456.789.123.456.789 987.654.321.987.654.321]";
+        let a = jam(big);
+        let b = jam(&format!("[{} {}]", big, big));
+        assert!(bits(&b) < 2 * bits(&a));
+
+        // A noun that is large if walked as a tree.
+        let mut noun = Noun::from(42u32);
+        let mut sizes = Vec::new();
+        for _ in 0..16 {
+            noun = Noun::cell(noun.clone(), noun);
+            sizes.push(bits(&noun.jam()));
+        }
+        assert!(sizes[15] - sizes[14] < 16);
+        round_trip(&noun);
+    }
+}
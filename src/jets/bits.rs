@@ -0,0 +1,414 @@
+//! Jets for the bit-block gates of `hoon.hoon`.
+//!
+//! Atoms are handled as blocks of `2^a` bits, where `a` is the block size
+//! exponent or bloq. A bite is either a bloq, or a `[bloq step]` cell for
+//! runs of `step` blocks. Gates that take a bite also accept the older
+//! `[bloq step atom]` sample.
+
+use std::cmp;
+use digit_slice::msb;
+use {Shape, Noun, NockResult, FromNoun};
+use super::{Dashboard, Jet};
+
+/// Largest atom in bytes the jets will build, the size of Vere's default
+/// loom. Larger results are left to Nock.
+const MAX_BYTES: usize = 1 << 31;
+
+/// Register the jets for the bit-block gates in the `two` core of
+/// `hoon.hoon`.
+pub fn install(dashboard: &mut Dashboard) {
+    let jets: [(&str, Jet); 12] = [("two/met", met),
+                                   ("two/lsh", lsh),
+                                   ("two/rsh", rsh),
+                                   ("two/end", end),
+                                   ("two/cat", cat),
+                                   ("two/cut", cut),
+                                   ("two/can", can),
+                                   ("two/rep", rep),
+                                   ("two/rip", rip),
+                                   ("two/mix", mix),
+                                   ("two/con", con),
+                                   ("two/dis", dis)];
+    for &(label, jet) in &jets {
+        dashboard.register_named(label, Noun::from(2u32), jet);
+    }
+}
+
+/// `(met a b)`, the number of bloq `a` blocks in `b`.
+pub fn met(core: &Noun) -> Option<NockResult> {
+    let s = sample(core)?;
+    let (a, b) = pair(&s)?;
+    let size = block(small(a)?, 1)?;
+    done(Noun::from(bit_len(digits(b)?).div_ceil(size)))
+}
+
+/// `(lsh a b)`, shift `b` up by the bite `a`.
+pub fn lsh(core: &Noun) -> Option<NockResult> {
+    let (n, b) = shift(core)?;
+    let b = trim(digits(&b)?);
+    if b.is_empty() {
+        return done(Noun::from(0u32));
+    }
+    let mut out = Vec::new();
+    or_at(&mut out, b, n)?;
+    done(atom(out))
+}
+
+/// `(rsh a b)`, shift `b` down by the bite `a`.
+pub fn rsh(core: &Noun) -> Option<NockResult> {
+    let (n, b) = shift(core)?;
+    done(atom(slice(digits(&b)?, n, usize::MAX)))
+}
+
+/// `(end a b)`, the first bite `a` of `b`.
+pub fn end(core: &Noun) -> Option<NockResult> {
+    let (n, b) = shift(core)?;
+    done(atom(slice(digits(&b)?, 0, n)))
+}
+
+/// `(cat a b c)`, concatenate `c` after `b` at bloq `a` alignment.
+pub fn cat(core: &Noun) -> Option<NockResult> {
+    let s = sample(core)?;
+    let (a, b, c) = s.get_122()?;
+    let size = block(small(a)?, 1)?;
+    let (b, c) = (trim(digits(b)?), trim(digits(c)?));
+    let mut out = b.to_vec();
+    or_at(&mut out, c, bit_len(b).div_ceil(size).checked_mul(size)?)?;
+    done(atom(out))
+}
+
+/// `(cut a [b c] d)`, `c` blocks of bloq `a` from `d`, starting at block
+/// `b`.
+pub fn cut(core: &Noun) -> Option<NockResult> {
+    let s = sample(core)?;
+    let (a, range, d) = s.get_122()?;
+    let (b, c) = pair(range)?;
+    let a = small(a)?;
+    let (from, len) = (block(a, small(b)?)?, block(a, small(c)?)?);
+    done(atom(slice(digits(d)?, from, len)))
+}
+
+/// `(can a b)`, assemble the list `b` of `[step atom]` runs of bloq `a`.
+pub fn can(core: &Noun) -> Option<NockResult> {
+    let s = sample(core)?;
+    let (a, b) = pair(&s)?;
+    let a = small(a)?;
+    let mut out = Vec::new();
+    let mut pos = 0usize;
+    for item in Vec::<Noun>::from_noun(b).ok()? {
+        let (step, x) = pair(&item)?;
+        let n = block(a, small(step)?)?;
+        or_at(&mut out, &slice(digits(x)?, 0, n), pos)?;
+        pos = pos.checked_add(n)?;
+    }
+    done(atom(out))
+}
+
+/// `(rep a b)`, assemble the list `b` of bites `a`.
+pub fn rep(core: &Noun) -> Option<NockResult> {
+    let s = sample(core)?;
+    let (a, b) = pair(&s)?;
+    let n = bite(a)?;
+    let mut out = Vec::new();
+    let mut pos = 0usize;
+    for x in Vec::<Noun>::from_noun(b).ok()? {
+        or_at(&mut out, &slice(digits(&x)?, 0, n), pos)?;
+        pos = pos.checked_add(n)?;
+    }
+    done(atom(out))
+}
+
+/// `(rip a b)`, split `b` into a list of bites `a`.
+pub fn rip(core: &Noun) -> Option<NockResult> {
+    let s = sample(core)?;
+    let (a, b) = pair(&s)?;
+    let n = bite(a)?;
+    if n == 0 {
+        // The gate never finishes splitting with an empty bite.
+        return None;
+    }
+    let b = digits(b)?;
+    let count = bit_len(b).div_ceil(n);
+    let list = (0..count).rev().fold(Noun::from(0u32), |acc, i| {
+        Noun::cell(atom(slice(b, i * n, n)), acc)
+    });
+    done(list)
+}
+
+/// `(mix a b)`, bitwise exclusive or.
+pub fn mix(core: &Noun) -> Option<NockResult> {
+    bitwise(core, |x, y| x ^ y)
+}
+
+/// `(con a b)`, bitwise or.
+pub fn con(core: &Noun) -> Option<NockResult> {
+    bitwise(core, |x, y| x | y)
+}
+
+/// `(dis a b)`, bitwise and.
+pub fn dis(core: &Noun) -> Option<NockResult> {
+    bitwise(core, |x, y| x & y)
+}
+
+fn bitwise<F: Fn(u8, u8) -> u8>(core: &Noun, f: F) -> Option<NockResult> {
+    let s = sample(core)?;
+    let (a, b) = pair(&s)?;
+    let (a, b) = (digits(a)?, digits(b)?);
+    let out = (0..cmp::max(a.len(), b.len()))
+                  .map(|i| {
+                      f(a.get(i).cloned().unwrap_or(0),
+                        b.get(i).cloned().unwrap_or(0))
+                  })
+                  .collect();
+    done(atom(out))
+}
+
+fn pair(n: &Noun) -> Option<(&Noun, &Noun)> {
+    match n.get() {
+        Shape::Cell(a, b) => Some((a, b)),
+        _ => None,
+    }
+}
+
+fn digits(n: &Noun) -> Option<&[u8]> {
+    match n.get() {
+        Shape::Atom(x) => Some(x),
+        _ => None,
+    }
+}
+
+fn small(n: &Noun) -> Option<usize> {
+    usize::from_noun(n).ok()
+}
+
+fn sample(core: &Noun) -> Option<Noun> {
+    ::get_axis(&Noun::from(6u32), core).ok()
+}
+
+fn done(x: Noun) -> Option<NockResult> {
+    Some(Ok(x))
+}
+
+/// Size in bits of `step` blocks of bloq `a`.
+fn block(a: usize, step: usize) -> Option<usize> {
+    if a >= usize::BITS as usize {
+        return None;
+    }
+    step.checked_mul(1 << a)
+}
+
+/// Size in bits of a bite.
+fn bite(a: &Noun) -> Option<usize> {
+    match a.get() {
+        Shape::Cell(bloq, step) => block(small(bloq)?, small(step)?),
+        _ => block(small(a)?, 1),
+    }
+}
+
+/// Bit count and atom of a `[bite atom]` or `[bloq step atom]` sample.
+fn shift(core: &Noun) -> Option<(usize, Noun)> {
+    let s = sample(core)?;
+    let (a, rest) = pair(&s)?;
+    let n = match rest.get() {
+        Shape::Cell(step, b) => (block(small(a)?, small(step)?)?, b),
+        _ => (bite(a)?, rest),
+    };
+    Some((n.0, n.1.clone()))
+}
+
+fn trim(x: &[u8]) -> &[u8] {
+    let len = x.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &x[..len]
+}
+
+fn bit_len(x: &[u8]) -> usize {
+    msb(trim(x))
+}
+
+/// Build an atom from digits that may have trailing zeros.
+fn atom(mut digits: Vec<u8>) -> Noun {
+    let len = trim(&digits).len();
+    digits.truncate(len);
+    Noun::atom(&digits)
+}
+
+/// Bits `from` to `from + len` of `x`.
+fn slice(x: &[u8], from: usize, len: usize) -> Vec<u8> {
+    let end = cmp::min(from.saturating_add(len), x.len() * 8);
+    if from >= end {
+        return Vec::new();
+    }
+    let n = end - from;
+    let (byte, shift) = (from / 8, from % 8);
+    let mut out: Vec<u8> = (0..n.div_ceil(8))
+                               .map(|i| {
+                                   let lo = x[byte + i] >> shift;
+                                   let hi = match x.get(byte + i + 1) {
+                                       Some(&b) if shift > 0 => {
+                                           b << (8 - shift)
+                                       }
+                                       _ => 0,
+                                   };
+                                   lo | hi
+                               })
+                               .collect();
+    if !n.is_multiple_of(8) {
+        let last = out.len() - 1;
+        out[last] &= (1 << (n % 8)) - 1;
+    }
+    out
+}
+
+/// Or the bits of `x` into `out` starting from bit `at`.
+///
+/// Fails if `out` would grow past `MAX_BYTES`.
+fn or_at(out: &mut Vec<u8>, x: &[u8], at: usize) -> Option<()> {
+    if x.is_empty() {
+        return Some(());
+    }
+    let (byte, shift) = (at / 8, at % 8);
+    let len = byte.checked_add(x.len())?.checked_add(1)?;
+    if len > MAX_BYTES {
+        return None;
+    }
+    if out.len() < len {
+        out.resize(len, 0);
+    }
+    for (i, &b) in x.iter().enumerate() {
+        out[byte + i] |= b << shift;
+        if shift > 0 {
+            out[byte + i + 1] |= b >> (8 - shift);
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+    use num::traits::{One, ToPrimitive};
+    use {Noun, FromNoun};
+    use jets::Jet;
+    use super::*;
+
+    fn jet(f: Jet, sample: &str) -> Option<String> {
+        let core: Noun = format!("[[0 0] {} 0]", sample).parse().unwrap();
+        f(&core).map(|x| format!("{}", x.unwrap()))
+    }
+
+    fn is(f: Jet, sample: &str, value: &str) {
+        assert_eq!(jet(f, sample), Some(value.to_string()), "{}", sample);
+    }
+
+    #[test]
+    fn test_bits() {
+        is(met, "[0 0]", "0");
+        is(met, "[0 255]", "8");
+        is(met, "[3 255]", "1");
+        is(met, "[3 256]", "2");
+        is(met, "[5 18446744073709551616]", "3");
+
+        // Bite and [bloq step atom] samples.
+        is(lsh, "[0 1]", "2");
+        is(lsh, "[3 1]", "256");
+        is(lsh, "[[0 3] 1]", "8");
+        is(lsh, "[0 3 1]", "8");
+        is(lsh, "[[6 1] 1]", "18.446.744.073.709.551.616");
+        is(lsh, "[3 0]", "0");
+        is(rsh, "[3 65535]", "255");
+        is(rsh, "[[0 4] 255]", "15");
+        is(rsh, "[0 4 255]", "15");
+        is(rsh, "[3 255]", "0");
+        is(end, "[3 65535]", "255");
+        is(end, "[[0 4] 255]", "15");
+        is(end, "[0 4 255]", "15");
+        is(end, "[[3 4] 255]", "255");
+
+        is(cat, "[3 1 2]", "513");
+        is(cat, "[0 1 1]", "3");
+        is(cat, "[3 0 2]", "2");
+        is(cat, "[3 256 1]", "65.792");
+        is(cut, "[0 [1 3] 255]", "7");
+        is(cut, "[3 [1 1] 4660]", "18");
+        is(cut, "[3 [2 1] 4660]", "0");
+        is(can, "[3 [1 1] [1 2] 0]", "513");
+        is(can, "[0 [2 7] [1 1] 0]", "7");
+        is(can, "[3 0]", "0");
+
+        is(rep, "[3 [1 2 3 0]]", "197.121");
+        is(rep, "[[0 2] [3 2 0]]", "11");
+        is(rep, "[0 [7 7 0]]", "3");
+        is(rip, "[3 197121]", "[1 2 3 0]");
+        is(rip, "[[0 2] 11]", "[3 2 0]");
+        is(rip, "[3 0]", "0");
+        is(rip, "[3 256]", "[0 1 0]");
+        assert_eq!(jet(rip, "[[3 0] 256]"), None);
+
+        is(mix, "[12 10]", "6");
+        is(mix, "[256 256]", "0");
+        is(con, "[12 10]", "14");
+        is(con, "[256 1]", "257");
+        is(dis, "[12 10]", "8");
+        is(dis, "[256 255]", "0");
+
+        // Bad samples are left to Nock.
+        assert_eq!(jet(met, "[0 [1 2]]"), None);
+        assert_eq!(jet(lsh, "[64 1]"), None);
+        assert_eq!(jet(mix, "[1 [1 2]]"), None);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // Compare with the definitions of hoon.hoon in terms of arithmetic.
+        fn run(f: Jet, sample: Noun) -> BigUint {
+            let core = Noun::cell(Noun::from(0u32),
+                                  Noun::cell(sample, Noun::from(0u32)));
+            BigUint::from_noun(&f(&core).unwrap().unwrap()).unwrap()
+        }
+        let bex = |n: usize| BigUint::one() << n;
+
+        let x: BigUint = "123456789012345678901234567890".parse().unwrap();
+        for a in 0..6usize {
+            for step in 0..40usize {
+                let n = step << a;
+                let bite = Noun::cell(Noun::from(a), Noun::from(step));
+                let sample = Noun::cell(bite, Noun::from(x.clone()));
+                assert_eq!(run(lsh, sample.clone()), &x * bex(n));
+                assert_eq!(run(rsh, sample.clone()), &x / bex(n));
+                assert_eq!(run(end, sample), &x % bex(n));
+            }
+            let sample = Noun::cell(Noun::from(a), Noun::from(x.clone()));
+            let m = run(met, sample).to_usize().unwrap();
+            assert!(x < bex(m << a) && x >= bex((m - 1) << a));
+        }
+    }
+
+    #[test]
+    fn test_cords() {
+        // Worked by hand from the definitions in hoon.hoon.
+        is(met, "[3 6513249]", "3");
+        is(met, "[0 6513249]", "23");
+        is(lsh, "[[3 1] 97]", "24.832");
+        is(rsh, "[[3 1] 6513249]", "25.442");
+        is(end, "[[3 2] 6513249]", "25.185");
+        is(cat, "[3 97 25442]", "6.513.249");
+        is(cut, "[3 [1 1] 6513249]", "98");
+        is(can, "[3 [1 97] [2 25442] 0]", "6.513.249");
+        is(rep, "[3 [97 98 99 0]]", "6.513.249");
+        is(rip, "[3 6513249]", "[97 98 99 0]");
+        is(mix, "[6513249 25185]", "6.488.064");
+    }
+
+    #[test]
+    fn test_huge() {
+        // Results too large to build are left to Nock.
+        assert_eq!(jet(lsh, "[[0 4611686018427387904] 1]"), None);
+        assert_eq!(jet(lsh, "[[0 18446744073709551615] 1]"), None);
+        assert_eq!(jet(lsh, "[[3 4294967296] 1]"), None);
+        assert_eq!(jet(can, "[3 [4294967296 0] [1 1] 0]"), None);
+        assert_eq!(jet(rep, "[[3 4294967296] [0 1 0]]"), None);
+        // Zeros take no room wherever they go.
+        is(lsh, "[[3 4294967296] 0]", "0");
+        is(can, "[3 [1 1] [4294967296 0] 0]", "1");
+    }
+}
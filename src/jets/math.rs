@@ -0,0 +1,231 @@
+//! Jets for the arithmetic gates of `hoon.hoon`.
+//!
+//! Cases where the gate crashes, such as decrementing zero, are left to the
+//! Nock formula, so the evaluation fails with the error it would have
+//! without the jet.
+
+use num::{BigUint, Integer, Zero};
+use {Noun, NockResult, FromNoun};
+use super::{Dashboard, Jet};
+
+/// Register the jets for the gates in the `one` core of `hoon.hoon`.
+pub fn install(dashboard: &mut Dashboard) {
+    let jets: [(&str, Jet); 13] = [("one/add", add),
+                                   ("one/dec", dec),
+                                   ("one/sub", sub),
+                                   ("one/mul", mul),
+                                   ("one/div", div),
+                                   ("one/mod", mod_),
+                                   ("one/dvr", dvr),
+                                   ("one/gte", gte),
+                                   ("one/gth", gth),
+                                   ("one/lte", lte),
+                                   ("one/lth", lth),
+                                   ("one/max", max),
+                                   ("one/min", min)];
+    for &(label, jet) in &jets {
+        dashboard.register_named(label, Noun::from(2u32), jet);
+    }
+}
+
+/// Get the sample of a gate.
+fn sample<T: FromNoun>(core: &Noun) -> Option<T> {
+    ::get_axis(&Noun::from(6u32), core)
+        .ok()
+        .and_then(|x| T::from_noun(&x).ok())
+}
+
+fn pair(core: &Noun) -> Option<(BigUint, BigUint)> {
+    sample(core)
+}
+
+fn done<T: ::ToNoun>(x: T) -> Option<NockResult> {
+    Some(Ok(Noun::from(x)))
+}
+
+/// `(add a b)`, sum.
+pub fn add(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    done(a + b)
+}
+
+/// `(dec a)`, decrement.
+pub fn dec(core: &Noun) -> Option<NockResult> {
+    let a: BigUint = sample(core)?;
+    if a.is_zero() {
+        return None;
+    }
+    done(a - BigUint::from(1u32))
+}
+
+/// `(sub a b)`, difference, crashes if `b` is larger.
+pub fn sub(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    if b > a {
+        return None;
+    }
+    done(a - b)
+}
+
+/// `(mul a b)`, product.
+pub fn mul(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    done(a * b)
+}
+
+/// `(div a b)`, quotient, crashes if `b` is zero.
+pub fn div(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    if b.is_zero() {
+        return None;
+    }
+    done(a / b)
+}
+
+/// `(mod a b)`, remainder, crashes if `b` is zero.
+pub fn mod_(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    if b.is_zero() {
+        return None;
+    }
+    done(a % b)
+}
+
+/// `(dvr a b)`, quotient and remainder, crashes if `b` is zero.
+pub fn dvr(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    if b.is_zero() {
+        return None;
+    }
+    done(a.div_rem(&b))
+}
+
+/// `(gte a b)`, whether `a` is greater than or equal to `b`.
+pub fn gte(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    done(a >= b)
+}
+
+/// `(gth a b)`, whether `a` is greater than `b`.
+pub fn gth(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    done(a > b)
+}
+
+/// `(lte a b)`, whether `a` is less than or equal to `b`.
+pub fn lte(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    done(a <= b)
+}
+
+/// `(lth a b)`, whether `a` is less than `b`.
+pub fn lth(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    done(a < b)
+}
+
+/// `(max a b)`, the larger number.
+pub fn max(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    done(if a > b { a } else { b })
+}
+
+/// `(min a b)`, the smaller number.
+pub fn min(core: &Noun) -> Option<NockResult> {
+    let (a, b) = pair(core)?;
+    done(if a < b { a } else { b })
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+    use {Nock, Noun};
+    use jets::{Dashboard, Jet};
+    use super::*;
+
+    fn gate(sample: &str) -> Noun {
+        format!("[[0 0] {} 0]", sample).parse().unwrap()
+    }
+
+    fn jet(f: Jet, sample: &str) -> Option<String> {
+        f(&gate(sample)).map(|x| format!("{}", x.unwrap()))
+    }
+
+    #[test]
+    fn test_math() {
+        assert_eq!(jet(add, "[2 3]"), Some("5".to_string()));
+        assert_eq!(jet(add, "[18446744073709551615 1]"),
+                   Some("18.446.744.073.709.551.616".to_string()));
+        assert_eq!(jet(dec, "18446744073709551616"),
+                   Some("18.446.744.073.709.551.615".to_string()));
+        assert_eq!(jet(dec, "0"), None);
+        assert_eq!(jet(sub, "[10 3]"), Some("7".to_string()));
+        assert_eq!(jet(sub, "[3 10]"), None);
+        assert_eq!(jet(mul, "[6 7]"), Some("42".to_string()));
+        assert_eq!(jet(div, "[43 7]"), Some("6".to_string()));
+        assert_eq!(jet(div, "[43 0]"), None);
+        assert_eq!(jet(mod_, "[43 7]"), Some("1".to_string()));
+        assert_eq!(jet(mod_, "[43 0]"), None);
+        assert_eq!(jet(dvr, "[43 7]"), Some("[6 1]".to_string()));
+        assert_eq!(jet(dvr, "[43 0]"), None);
+        assert_eq!(jet(gte, "[3 3]"), Some("0".to_string()));
+        assert_eq!(jet(gte, "[2 3]"), Some("1".to_string()));
+        assert_eq!(jet(gth, "[3 3]"), Some("1".to_string()));
+        assert_eq!(jet(gth, "[4 3]"), Some("0".to_string()));
+        assert_eq!(jet(lte, "[3 3]"), Some("0".to_string()));
+        assert_eq!(jet(lte, "[4 3]"), Some("1".to_string()));
+        assert_eq!(jet(lth, "[3 3]"), Some("1".to_string()));
+        assert_eq!(jet(lth, "[2 3]"), Some("0".to_string()));
+        assert_eq!(jet(max, "[2 3]"), Some("3".to_string()));
+        assert_eq!(jet(min, "[2 3]"), Some("2".to_string()));
+        // Bad samples are left to Nock.
+        assert_eq!(jet(add, "[[1 2] 3]"), None);
+        assert_eq!(jet(dec, "[1 2]"), None);
+    }
+
+    struct JetVM(Dashboard);
+    impl Nock for JetVM {
+        fn jets(&mut self) -> Option<&mut Dashboard> {
+            Some(&mut self.0)
+        }
+    }
+
+    #[test]
+    fn test_dec() {
+        // Decrement by counting up, as in hoon.hoon, declared with the same
+        // %fast hints.
+        let root: Noun = "[[1 0] 0]".parse().unwrap();
+        let one = Noun::cell("[1 0]".parse().unwrap(), root.clone());
+        let dec_gate = Noun::cell("[8 [1 0] 8 [1 6 [5 [0 30] 4 0 6] [0 6] 9 2 \
+                                   [0 2] [4 0 6] 0 7] 9 2 0 1]"
+                                      .parse()
+                                      .unwrap(),
+                                  Noun::cell(Noun::from(0u32), one.clone()));
+        let call = "[9 2 10 [6 0 3] 0 2]".parse::<Noun>().unwrap();
+
+        let mut vm = JetVM(Dashboard::new());
+        install(&mut vm.0);
+        let clue = |x: &str| x.parse::<Noun>().unwrap();
+        // %k.140, %one and %dec.
+        assert!(vm.0.declare(&root, &clue("[[107 140] [1 0] 0]")));
+        assert!(vm.0.declare(&one, &clue("[6.647.407 [0 3] 0]")));
+        assert!(vm.0.declare(&dec_gate, &clue("[6.514.020 [0 7] 0]")));
+        assert_eq!(vm.0.label(&dec_gate), Some("k140/one/dec"));
+
+        // The jet agrees with the formula.
+        vm.0.set_checking(true);
+        for i in 1..20u32 {
+            assert_eq!(vm.nock_on(Noun::cell(dec_gate.clone(), Noun::from(i)),
+                                  call.clone()),
+                       Ok(Noun::from(i - 1)));
+        }
+        assert_eq!(vm.0.mismatches(), &[]);
+
+        // Counting up to this would never finish.
+        vm.0.set_checking(false);
+        let big = BigUint::from(1u32) << 100;
+        assert_eq!(vm.nock_on(Noun::cell(dec_gate, Noun::from(big.clone())),
+                              call),
+                   Ok(Noun::from(big - BigUint::from(1u32))));
+    }
+}
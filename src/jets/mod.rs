@@ -0,0 +1,324 @@
+//! Native implementations of Nock arms.
+
+use std::collections::HashMap;
+use std::hash;
+use std::mem;
+use fnv;
+use num::BigUint;
+use {Shape, Noun, NockResult, FromNoun};
+
+pub mod bits;
+pub mod math;
+pub mod sha;
+
+/// Native implementation of an arm.
+///
+/// A jet gets the core the arm is called on. It must produce exactly the
+/// same result as evaluating the arm, or `None` to fall back to evaluating
+/// the arm as Nock.
+pub type Jet = fn(&Noun) -> Option<NockResult>;
+
+type Fnv = hash::BuildHasherDefault<fnv::FnvHasher>;
+
+/// Registry of jets for the cores a Nock program uses.
+///
+/// Jets can be registered for a known battery and arm axis, or by the label
+/// of a core. Cores get labels by being declared with the `%fast` hint, as
+/// Hoon's `~%` rune does, after which jets registered for the label are
+/// matched to the core's battery.
+///
+/// In checking mode, every arm with a jet is also evaluated as Nock, and
+/// results that differ from the jet's are recorded as `Mismatch`es. The
+/// evaluation always continues with the Nock result.
+#[derive(Default)]
+pub struct Dashboard {
+    /// Jets by battery and arm axis.
+    jets: HashMap<(Noun, Noun), Jet, Fnv>,
+    /// Jets by core label and arm axis, waiting to be matched to batteries.
+    named: Vec<(String, Noun, Jet)>,
+    /// Labels of the cores declared with `%fast`, by battery.
+    cores: HashMap<Noun, String, Fnv>,
+    checking: bool,
+    mismatches: Vec<Mismatch>,
+}
+
+/// Jet that produced a different result than the arm it replaces.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mismatch {
+    /// Label of the core, if it was declared with `%fast`.
+    pub label: Option<String>,
+    pub axis: Noun,
+    /// Core the arm was called on. For a gate, the sample is at axis 6.
+    pub core: Noun,
+    /// Result of the jet.
+    pub jet: NockResult,
+    /// Result of evaluating the arm.
+    pub nock: NockResult,
+}
+
+impl Dashboard {
+    pub fn new() -> Dashboard {
+        Default::default()
+    }
+
+    /// Register a jet for an arm of cores with the given battery.
+    pub fn register(&mut self, battery: Noun, axis: Noun, jet: Jet) {
+        self.jets.insert((battery, axis), jet);
+    }
+
+    /// Register a jet for an arm of cores with the given label.
+    ///
+    /// Labels are the `%fast` names of a core and its parents, outermost
+    /// first and separated by `/`, such as `k141/one/dec`. The jet is used
+    /// for every declared core whose label ends with the given one, so `dec`
+    /// and `one/dec` both match that core.
+    pub fn register_named(&mut self, label: &str, axis: Noun, jet: Jet) {
+        for (battery, core_label) in &self.cores {
+            if label_matches(core_label, label) {
+                self.jets.insert((battery.clone(), axis.clone()), jet);
+            }
+        }
+        self.named.push((label.to_string(), axis, jet));
+    }
+
+    /// Declare a core from a `%fast` hint clue.
+    ///
+    /// The clue is `[name parent hooks]`, where `name` is a cord, `parent` is
+    /// `[0 a]` for a parent core at axis `a` of the core or `[1 0]` for a
+    /// root core. Returns whether the core was declared.
+    pub fn declare(&mut self, core: &Noun, clue: &Noun) -> bool {
+        let (name, parent) = match clue.get_122() {
+            Some((name, parent, _)) => (name, parent),
+            None => return false,
+        };
+        let name = match core_name(name) {
+            Some(name) => name,
+            None => return false,
+        };
+        let battery = match core.get() {
+            Shape::Cell(battery, _) => battery,
+            _ => return false,
+        };
+
+        let label = match parent.get() {
+            Shape::Cell(op, axis) if op.as_u32() == Some(0) => {
+                let parent = match ::get_axis(axis, core) {
+                    Ok(parent) => parent,
+                    Err(_) => return false,
+                };
+                match self.label(&parent) {
+                    Some(prefix) => format!("{}/{}", prefix, name),
+                    None => return false,
+                }
+            }
+            Shape::Cell(op, x) if op.as_u32() == Some(1) &&
+                                  x.as_u32() == Some(0) => name,
+            _ => return false,
+        };
+
+        for &(ref suffix, ref axis, jet) in &self.named {
+            if label_matches(&label, suffix) {
+                self.jets.insert((battery.clone(), axis.clone()), jet);
+            }
+        }
+        self.cores.insert(battery.clone(), label);
+        true
+    }
+
+    /// Return the label of a declared core.
+    pub fn label(&self, core: &Noun) -> Option<&str> {
+        match core.get() {
+            Shape::Cell(battery, _) => {
+                self.cores.get(battery).map(|x| x.as_str())
+            }
+            _ => None,
+        }
+    }
+
+    /// Turn checking mode on or off.
+    pub fn set_checking(&mut self, checking: bool) {
+        self.checking = checking;
+    }
+
+    pub fn checking(&self) -> bool {
+        self.checking
+    }
+
+    /// Mismatches found in checking mode so far.
+    pub fn mismatches(&self) -> &[Mismatch] {
+        &self.mismatches
+    }
+
+    /// Remove and return the mismatches found so far.
+    pub fn take_mismatches(&mut self) -> Vec<Mismatch> {
+        mem::take(&mut self.mismatches)
+    }
+
+    /// Compare the result of a jet with the result of evaluating its arm.
+    ///
+    /// Both crashing counts as agreeing, the jet need not crash the same
+    /// way.
+    pub fn check(&mut self,
+                 core: &Noun,
+                 axis: &Noun,
+                 jet: &NockResult,
+                 nock: &NockResult) {
+        let agree = match (jet, nock) {
+            (Ok(a), Ok(b)) => a == b,
+            (Err(_), Err(_)) => true,
+            _ => false,
+        };
+        if !agree {
+            self.mismatches.push(Mismatch {
+                label: self.label(core).map(|x| x.to_string()),
+                axis: axis.clone(),
+                core: core.clone(),
+                jet: jet.clone(),
+                nock: nock.clone(),
+            });
+        }
+    }
+
+    /// Run the jet for an arm of a core, if there is one.
+    pub fn call(&self, core: &Noun, axis: &Noun) -> Option<NockResult> {
+        if let Shape::Cell(battery, _) = core.get() {
+            let key = (battery.clone(), axis.clone());
+            if let Some(jet) = self.jets.get(&key) {
+                return jet(core);
+            }
+        }
+        None
+    }
+}
+
+/// Decode the name of a `%fast` core, a cord or a `[cord version]` cell.
+fn core_name(name: &Noun) -> Option<String> {
+    match name.get() {
+        Shape::Atom(_) => String::from_noun(name).ok(),
+        Shape::Cell(a, b) => {
+            let version = match b.get() {
+                Shape::Atom(x) => BigUint::from_bytes_le(x),
+                _ => return None,
+            };
+            String::from_noun(a).ok().map(|a| format!("{}{}", a, version))
+        }
+    }
+}
+
+fn label_matches(label: &str, suffix: &str) -> bool {
+    label == suffix ||
+    (label.ends_with(suffix) &&
+     label[..label.len() - suffix.len()].ends_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use {Nock, Noun, Shape, NockResult};
+    use super::{Dashboard, Mismatch};
+
+    struct JetVM(Dashboard);
+    impl Nock for JetVM {
+        fn jets(&mut self) -> Option<&mut Dashboard> {
+            Some(&mut self.0)
+        }
+    }
+
+    /// Bump the sample of a gate.
+    fn bump(core: &Noun) -> Option<NockResult> {
+        let x = ::get_axis(&Noun::from(6u32), core).ok()?.as_u32()?;
+        Some(Ok(Noun::from(x + 1)))
+    }
+
+    fn eval(vm: &mut JetVM, input: &str) -> NockResult {
+        let (s, f) = match input.parse::<Noun>().unwrap().get() {
+            Shape::Cell(s, f) => (s.clone(), f.clone()),
+            _ => unreachable!(),
+        };
+        vm.nock_on(s, f)
+    }
+
+    // The gates below have the crashing arm [0 0], so they only work when
+    // jetted.
+
+    #[test]
+    fn test_battery() {
+        let mut vm = JetVM(Dashboard::new());
+        assert!(eval(&mut vm, "[[[0 0] 41 0] 9 2 0 1]").is_err());
+
+        vm.0.register("[0 0]".parse().unwrap(), Noun::from(2u32), bump);
+        assert_eq!(eval(&mut vm, "[[[0 0] 41 0] 9 2 0 1]"),
+                   Ok(Noun::from(42u32)));
+        // Only the registered arm is jetted.
+        assert!(eval(&mut vm, "[[[0 0] 41 0] 9 3 0 1]").is_err());
+        // Jets can fall back to Nock.
+        assert!(eval(&mut vm, "[[[0 0] [41 41] 0] 9 2 0 1]").is_err());
+    }
+
+    #[test]
+    fn test_fast() {
+        // The %fast hint declares cores %root and %root/dec.
+        let program = "[0 7 [11 [1953718630 1 1953460082 [1 0] 0] 1 [1 0] 0] \
+                       7 [11 [1953718630 1 6514020 [0 7] 0] [1 0 0] [1 41] \
+                       0 1] 9 2 0 1]";
+
+        let mut vm = JetVM(Dashboard::new());
+        vm.0.register_named("root/dec", Noun::from(2u32), bump);
+        assert_eq!(eval(&mut vm, program), Ok(Noun::from(42u32)));
+        assert_eq!(vm.0.label(&"[[0 0] 41 [1 0] 0]".parse().unwrap()),
+                   Some("root/dec"));
+
+        let mut vm = JetVM(Dashboard::new());
+        vm.0.register_named("dec", Noun::from(2u32), bump);
+        assert_eq!(eval(&mut vm, program), Ok(Noun::from(42u32)));
+
+        let mut vm = JetVM(Dashboard::new());
+        vm.0.register_named("oot/dec", Noun::from(2u32), bump);
+        assert!(eval(&mut vm, program).is_err());
+        // Jets can be registered after their cores are declared.
+        vm.0.register_named("dec", Noun::from(2u32), bump);
+        assert_eq!(eval(&mut vm, "[[[0 0] 41 [1 0] 0] 9 2 0 1]"),
+                   Ok(Noun::from(42u32)));
+
+        // Cores with undeclared parents are not declared.
+        let mut vm = JetVM(Dashboard::new());
+        vm.0.register_named("dec", Noun::from(2u32), bump);
+        assert!(eval(&mut vm,
+                     "[0 7 [11 [1953718630 1 6514020 [0 7] 0] [1 0 0] [1 41] \
+                      0 1] 9 2 0 1]")
+                    .is_err());
+    }
+
+    #[test]
+    fn test_checking() {
+        let mut vm = JetVM(Dashboard::new());
+        vm.0.set_checking(true);
+        vm.0.register("[4 0 6]".parse().unwrap(), Noun::from(2u32), bump);
+        // Wrong jet for an arm that adds 2.
+        vm.0.register("[4 4 0 6]".parse().unwrap(), Noun::from(2u32), bump);
+        vm.0.register("[0 0]".parse().unwrap(), Noun::from(2u32), bump);
+
+        assert_eq!(eval(&mut vm, "[[[4 0 6] 41 0] 9 2 0 1]"),
+                   Ok(Noun::from(42u32)));
+        assert_eq!(vm.0.mismatches(), &[]);
+
+        // The evaluation goes on with the Nock result.
+        assert_eq!(eval(&mut vm, "[[[4 4 0 6] 41 0] 9 2 0 1]"),
+                   Ok(Noun::from(43u32)));
+        assert_eq!(vm.0.take_mismatches(),
+                   vec![Mismatch {
+                            label: None,
+                            axis: Noun::from(2u32),
+                            core: "[[4 4 0 6] 41 0]".parse().unwrap(),
+                            jet: Ok(Noun::from(42u32)),
+                            nock: Ok(Noun::from(43u32)),
+                        }]);
+        assert_eq!(vm.0.mismatches(), &[]);
+
+        // Arms that crash are caught when the evaluation fails.
+        assert!(eval(&mut vm, "[[[0 0] 41 0] 4 9 2 0 1]").is_err());
+        let mismatches = vm.0.take_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].jet, Ok(Noun::from(42u32)));
+        assert!(mismatches[0].nock.is_err());
+    }
+}
@@ -0,0 +1,75 @@
+//! Jets for the SHA-2 hash gates of `hoon.hoon`.
+
+use {Noun, NockResult, FromNoun};
+use super::{Dashboard, Jet};
+
+/// Register the jets for the hash gates.
+///
+/// The gates are matched by their own names, since they are declared in
+/// differently named cores across `hoon.hoon` versions.
+pub fn install(dashboard: &mut Dashboard) {
+    let jets: [(&str, Jet); 4] = [("shax", shax),
+                                  ("shay", shay),
+                                  ("shal", shal),
+                                  ("shas", shas)];
+    for &(label, jet) in &jets {
+        dashboard.register_named(label, Noun::from(2u32), jet);
+    }
+}
+
+fn sample(core: &Noun) -> Option<Noun> {
+    ::get_axis(&Noun::from(6u32), core).ok()
+}
+
+/// `(shax a)`, SHA-256 of an atom.
+pub fn shax(core: &Noun) -> Option<NockResult> {
+    sample(core)?.shax().ok().map(Ok)
+}
+
+/// `(shay a b)`, SHA-256 of the first `a` bytes of `b`.
+pub fn shay(core: &Noun) -> Option<NockResult> {
+    let (len, b): (usize, Noun) = FromNoun::from_noun(&sample(core)?).ok()?;
+    b.shay(len).ok().map(Ok)
+}
+
+/// `(shal a b)`, SHA-512 of the first `a` bytes of `b`.
+pub fn shal(core: &Noun) -> Option<NockResult> {
+    let (len, b): (usize, Noun) = FromNoun::from_noun(&sample(core)?).ok()?;
+    b.shal(len).ok().map(Ok)
+}
+
+/// `(shas a b)`, SHA-256 of `b` salted with `a`.
+pub fn shas(core: &Noun) -> Option<NockResult> {
+    let (salt, b): (Noun, Noun) = FromNoun::from_noun(&sample(core)?).ok()?;
+    b.shas(&salt).ok().map(Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use {Noun, ToNoun};
+    use jets::Jet;
+    use super::*;
+
+    fn jet(f: Jet, sample: Noun) -> Option<Noun> {
+        let core = Noun::cell(Noun::from(0u32),
+                              Noun::cell(sample, Noun::from(0u32)));
+        f(&core).map(|x| x.unwrap())
+    }
+
+    #[test]
+    fn test_sha() {
+        let abc = "abc".to_noun();
+        let len = Noun::from(3u32);
+        assert_eq!(jet(shax, abc.clone()), abc.shax().ok());
+        assert_eq!(jet(shay, Noun::cell(len.clone(), abc.clone())),
+                   abc.shax().ok());
+        assert_eq!(jet(shal, Noun::cell(len, abc.clone())), abc.shal(3).ok());
+        assert_eq!(jet(shas, Noun::cell(Noun::from(0u32), abc.clone())),
+                   abc.shax().unwrap().shax().ok());
+        // Cells and lengths too large to pad are left to Nock.
+        assert_eq!(jet(shax, Noun::cell(abc.clone(), abc.clone())), None);
+        let huge = Noun::from(1u64 << 62);
+        assert_eq!(jet(shay, Noun::cell(huge.clone(), abc.clone())), None);
+        assert_eq!(jet(shal, Noun::cell(huge, abc)), None);
+    }
+}
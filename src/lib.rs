@@ -51,8 +51,6 @@
 //!                     *[[*[a b] a] c]
 //! *[a 9 b c]          *[a 7 c 2 [0 1] 0 b]
 //!                     *[*[a c] 2 [0 1] 0 b]
-//! *[a 10 [b c] d]     *[a 8 c 7 [0 3] d]
-//! *[a 10 b c]         *[a c]
 //! *[a 10 [b c] d]     #[b *[a c] *[a d]]
 //! 
 //! *[a 11 [b c] d]     *[[*[a c] *[a d]] 0 3]
@@ -60,6 +58,13 @@
 //!
 //! *a                  *a
 //! ```
+//!
+//! This is the Nock 4K spec. The older Nock 5K spec, where opcode 10 is the
+//! hint, can be selected with `NockSpec`.
+//!
+//! Nouns are reference counted with `Rc` by default. Enabling the `sync`
+//! feature switches them to `Arc`, so that nouns are `Send` and `Sync` and
+//! can be shared between threads.
 
 #![crate_name="nock"]
 
@@ -67,20 +72,38 @@ extern crate num;
 extern crate fnv;
 
 use std::collections::HashMap;
+#[cfg(not(feature = "sync"))]
 use std::rc::Rc;
+#[cfg(feature = "sync")]
+use std::sync::Arc as Rc;
 use std::error::Error;
 use std::str;
 use std::fmt;
 use std::iter;
 use std::hash;
 use std::default;
-use num::BigUint;
+use std::any;
+use std::mem;
+use std::convert::TryFrom;
+use num::{BigInt, BigUint, Integer};
 pub use digit_slice::{DigitSlice, FromDigits, msb};
+pub use float::{Half, Quad};
 
-pub use nock::{Nock, get_axis};
+pub use nock::{Nock, NockSpec, DEFAULT_MAX_DEPTH, get_axis, edit_axis};
+pub use intern::Interner;
+pub use memo::Memo;
 
 mod digit_slice;
+pub mod fields;
+mod float;
+mod intern;
+mod jam;
+pub mod jets;
+mod memo;
 mod nock;
+mod order;
+mod sha;
+mod treap;
 
 /// A wrapper for referencing Noun-like patterns.
 #[derive(Copy, Clone)]
@@ -95,24 +118,31 @@ pub enum Shape<A, N> {
 /// ordered pair of nouns.
 ///
 /// Atoms are represented by a little-endian byte array of 8-bit digits.
-#[derive(Clone, PartialEq, Eq)]
+/// Atoms of up to 63 bits are stored inline without a heap allocation.
+#[derive(Clone)]
 pub struct Noun {
     hash: u32,
     value: Inner,
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone)]
 enum Inner {
+    /// Atom that fits in `DIRECT_MAX`.
+    Direct(u64),
     Atom(Rc<Vec<u8>>),
     Cell(Rc<Noun>, Rc<Noun>),
 }
 
+/// Largest atom that is stored inline.
+const DIRECT_MAX: u64 = (1 << 63) - 1;
+
 pub type NounShape<'a> = Shape<&'a [u8], &'a Noun>;
 
 impl Noun {
     /// Get a shape wrapper for the noun to examine its structure.
     pub fn get(&self) -> NounShape {
         match self.value {
+            Inner::Direct(ref x) => Shape::Atom(x.as_digits()),
             Inner::Atom(ref v) => Shape::Atom(v),
             Inner::Cell(ref a, ref b) => Shape::Cell(&*a, &*b),
         }
@@ -142,15 +172,51 @@ impl Noun {
         &*self as *const _ as usize
     }
 
+    /// Hash of the noun, the same as the one computed by current Urbit.
     pub fn mug(&self) -> u32 {
         self.hash
     }
 
+    /// Hash of the noun used by older Urbit versions.
+    ///
+    /// Unlike `mug`, this isn't cached and is computed anew on every call.
+    pub fn fnv_mug(&self) -> u32 {
+        // Mugs of the finished subnouns are kept on their own stack, so deep
+        // nouns don't overflow the native stack.
+        let mut stack = vec![(self, false)];
+        let mut mugs = Vec::new();
+        while let Some((noun, expanded)) = stack.pop() {
+            match noun.get() {
+                Shape::Atom(a) => mugs.push(fnv_atom(a, 2_166_136_261)),
+                Shape::Cell(a, b) => {
+                    if expanded {
+                        let q = mugs.pop().unwrap();
+                        let p = mugs.pop().unwrap();
+                        mugs.push(fnv_pair(p, q));
+                    } else {
+                        stack.push((noun, true));
+                        stack.push((b, false));
+                        stack.push((a, false));
+                    }
+                }
+            }
+        }
+        mugs[0]
+    }
+
     /// Build a new atom noun from a little-endian 8-bit digit sequence.
     pub fn atom(digits: &[u8]) -> Noun {
+        // Atoms are kept without trailing zeros, so that equal atoms always
+        // have the same representation.
+        let len = digits.iter().rposition(|&x| x != 0).map_or(0, |i| i + 1);
+        let digits = &digits[..len];
+        let value = match u64::from_digits(digits) {
+            Ok(x) if x <= DIRECT_MAX => Inner::Direct(x),
+            _ => Inner::Atom(Rc::new(digits.to_vec())),
+        };
         Noun {
-            hash: mug_atom(digits, 2_166_136_261),
-            value: Inner::Atom(Rc::new(digits.to_vec())),
+            hash: mug_atom(digits),
+            value,
         }
     }
 
@@ -172,10 +238,25 @@ impl Noun {
     /// Will not match atoms that are larger than 2^32, but is not guaranteed
     /// to match atoms that are smaller than 2^32 but not by much.
     pub fn as_u32(&self) -> Option<u32> {
-        if let Shape::Atom(ref digits) = self.get() {
-            u32::from_digits(digits).ok()
-        } else {
-            None
+        match self.value {
+            Inner::Direct(x) if x <= u32::MAX as u64 => Some(x as u32),
+            Inner::Direct(_) => None,
+            _ => {
+                if let Shape::Atom(ref digits) = self.get() {
+                    u32::from_digits(digits).ok()
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Match noun if it's an atom that fits in 64 bits.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value {
+            Inner::Direct(x) => Some(x),
+            Inner::Atom(ref digits) => u64::from_digits(digits).ok(),
+            Inner::Cell(_, _) => None,
         }
     }
 
@@ -189,45 +270,47 @@ impl Noun {
         where F: FnMut(Shape<&'a [u8], T>) -> T,
               T: Clone
     {
-        fn h<'a, F, T, S: hash::BuildHasher>(noun: &'a Noun,
-                                             memo: &mut HashMap<usize, T, S>,
-                                             f: &mut F)
-                                             -> T
-            where F: FnMut(Shape<&'a [u8], T>) -> T,
-                  T: Clone
-        {
-            let key = noun.addr();
+        let fnv = hash::BuildHasherDefault::<fnv::FnvHasher>::default();
+        let mut memo: HashMap<usize, T, _> = HashMap::with_hasher(fnv);
+        // Post-order walk with a heap stack, so deep nouns don't overflow
+        // the native stack.
+        let mut stack = vec![(self, false)];
 
+        while let Some((noun, expanded)) = stack.pop() {
+            let key = noun.addr();
             if memo.contains_key(&key) {
-                memo.get(&key).unwrap().clone()
-            } else {
-                let ret = match noun.get() {
-                    Shape::Atom(x) => f(Shape::Atom(x)),
-                    Shape::Cell(ref a, ref b) => {
-                        let a = h(*a, memo, f);
-                        let b = h(*b, memo, f);
-                        f(Shape::Cell(a, b))
-                    }
-                };
-                memo.insert(key, ret.clone());
-                ret
+                continue;
             }
+            let ret = match noun.get() {
+                Shape::Atom(x) => f(Shape::Atom(x)),
+                Shape::Cell(a, b) => {
+                    if !expanded {
+                        stack.push((noun, true));
+                        stack.push((b, false));
+                        stack.push((a, false));
+                        continue;
+                    }
+                    let a = memo[&a.addr()].clone();
+                    let b = memo[&b.addr()].clone();
+                    f(Shape::Cell(a, b))
+                }
+            };
+            memo.insert(key, ret);
         }
 
-        let fnv = hash::BuildHasherDefault::<fnv::FnvHasher>::default();
-        h(self, &mut HashMap::with_hasher(fnv), &mut f)
+        memo.remove(&self.addr()).unwrap()
     }
 
     /// Return whether a noun is a list with more than n elements.
     fn is_wider_than(&self, n: usize) -> bool {
-        if n == 0 {
-            true
-        } else {
-            match self.get() {
-                Shape::Cell(_, ref a) => a.is_wider_than(n - 1),
-                _ => false,
+        let mut cur = self;
+        for _ in 0..n {
+            match cur.get() {
+                Shape::Cell(_, b) => cur = b,
+                _ => return false,
             }
         }
+        true
     }
 
     /// 3-letter abbrevation identifier for noun.
@@ -256,49 +339,94 @@ impl Noun {
         const MAX_ATOM_BITS: usize = 128;
         const MAX_CELL_WIDTH: usize = 12;
 
-        match self.value {
-            Inner::Atom(ref n) => {
-                if abbrev && msb(n) > MAX_ATOM_BITS {
-                    // Print huge atoms as abbreviated glyphs
-                    return write!(f, "@{}", self.glyph());
+        enum Item<'a> {
+            Noun(&'a Noun),
+            Text(&'static str),
+        }
+
+        // Work through the output with a heap stack, so deep nouns don't
+        // overflow the native stack.
+        let mut stack = vec![Item::Noun(self)];
+        while let Some(item) = stack.pop() {
+            let noun = match item {
+                Item::Noun(noun) => noun,
+                Item::Text(s) => {
+                    f.write_str(s)?;
+                    continue;
                 }
-                // Print dot-separated integer.
-                let s = format!("{}", BigUint::from_digits(n).unwrap());
-                let phase = s.len() % 3;
-                for (i, c) in s.chars().enumerate() {
-                    if i > 0 && i % 3 == phase {
-                        try!(write!(f, "."));
+            };
+
+            match noun.get() {
+                Shape::Atom(n) => {
+                    if abbrev && msb(n) > MAX_ATOM_BITS {
+                        // Print huge atoms as abbreviated glyphs
+                        write!(f, "@{}", noun.glyph())?;
+                        continue;
+                    }
+                    // Print dot-separated integer.
+                    let s = format!("{}", BigUint::from_digits(n).unwrap());
+                    let phase = s.len() % 3;
+                    for (i, c) in s.chars().enumerate() {
+                        if i > 0 && i % 3 == phase {
+                            f.write_str(".")?;
+                        }
+                        write!(f, "{}", c)?;
                     }
-                    try!(write!(f, "{}", c));
                 }
-                Ok(())
-            }
 
-            Inner::Cell(ref a, ref b) => {
-                if abbrev && self.is_wider_than(MAX_CELL_WIDTH) {
-                    return write!(f, "[{}]", self.glyph());
+                Shape::Cell(a, b) => {
+                    if abbrev && noun.is_wider_than(MAX_CELL_WIDTH) {
+                        write!(f, "[{}]", noun.glyph())?;
+                        continue;
+                    }
+
+                    // List pretty-printer.
+                    let mut elts = vec![a];
+                    let mut cur = b;
+                    while let Shape::Cell(a, b) = cur.get() {
+                        elts.push(a);
+                        cur = b;
+                    }
+                    stack.push(Item::Text("]"));
+                    stack.push(Item::Noun(cur));
+                    for x in elts.into_iter().rev() {
+                        stack.push(Item::Text(" "));
+                        stack.push(Item::Noun(x));
+                    }
+                    stack.push(Item::Text("["));
                 }
+            }
+        }
+        Ok(())
+    }
+}
 
-                try!(write!(f, "["));
-                try!(a.print(f, abbrev));
-                try!(write!(f, " "));
-                // List pretty-printer.
-                let mut cur = b;
-                loop {
-                    match cur.value {
-                        Inner::Cell(ref a, ref b) => {
-                            try!(a.print(f, abbrev));
-                            try!(write!(f, " "));
-                            cur = b;
-                        }
-                        Inner::Atom(_) => {
-                            try!(cur.print(f, abbrev));
-                            return write!(f, "]");
-                        }
+impl Drop for Noun {
+    fn drop(&mut self) {
+        // Unlink the uniquely owned cells onto a heap stack instead of
+        // letting them drop recursively, so deep nouns don't overflow the
+        // native stack.
+        fn unlink(noun: &mut Noun, stack: &mut Vec<Rc<Noun>>) {
+            if let Inner::Cell(..) = noun.value {
+                let value = mem::replace(&mut noun.value, Inner::Direct(0));
+                if let Inner::Cell(mut a, mut b) = value {
+                    if Rc::get_mut(&mut a).is_some() {
+                        stack.push(a);
+                    }
+                    if Rc::get_mut(&mut b).is_some() {
+                        stack.push(b);
                     }
                 }
             }
         }
+
+        let mut stack = Vec::new();
+        unlink(self, &mut stack);
+        while let Some(rc) = stack.pop() {
+            if let Ok(mut noun) = Rc::try_unwrap(rc) {
+                unlink(&mut noun, &mut stack);
+            }
+        }
     }
 }
 
@@ -309,6 +437,39 @@ impl default::Default for Noun {
     }
 }
 
+impl PartialEq for Noun {
+    fn eq(&self, other: &Noun) -> bool {
+        // Walk the nouns with a heap stack, so deep nouns don't overflow the
+        // native stack.
+        let mut stack = vec![(self, other)];
+        while let Some((a, b)) = stack.pop() {
+            // Different mugs mean different nouns, the same object means
+            // the same noun.
+            if a.hash != b.hash {
+                return false;
+            }
+            if Noun::ptr_eq(a, b) {
+                continue;
+            }
+            match (a.get(), b.get()) {
+                (Shape::Atom(x), Shape::Atom(y)) => {
+                    if x != y {
+                        return false;
+                    }
+                }
+                (Shape::Cell(p1, q1), Shape::Cell(p2, q2)) => {
+                    stack.push((q1, q2));
+                    stack.push((p1, p2));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+impl Eq for Noun {}
+
 impl hash::Hash for Noun {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
         self.hash.hash(state);
@@ -331,7 +492,54 @@ impl iter::FromIterator<Noun> for Noun {
     }
 }
 
-fn mug_atom(a: &[u8], init: u32) -> u32 {
+fn mug_atom(a: &[u8]) -> u32 {
+    mum(0xcafe_babe, 0x7fff, a)
+}
+
+fn mug_pair(p: u32, q: u32) -> u32 {
+    // The mugs are concatenated into a single atom, `(cat 5 p q)`.
+    let mut key = p.to_le_bytes().to_vec();
+    key.extend_from_slice(q.as_digits());
+    mum(0xdead_beef, 0xfffe, &key)
+}
+
+/// Murmur3 hash folded into 31 bits, retrying with the next seed if the
+/// result is zero.
+fn mum(seed: u32, fallback: u32, key: &[u8]) -> u32 {
+    for i in 0..8 {
+        let c = murmur3(key, seed.wrapping_add(i));
+        let ret = (c >> 31) ^ (c & 0x7fffffff);
+        if ret != 0 {
+            return ret;
+        }
+    }
+    fallback
+}
+
+/// 32-bit x86 MurmurHash3.
+fn murmur3(key: &[u8], seed: u32) -> u32 {
+    fn scramble(k: u32) -> u32 {
+        k.wrapping_mul(0xcc9e_2d51).rotate_left(15).wrapping_mul(0x1b87_3593)
+    }
+
+    let mut h = seed;
+    for chunk in key.chunks(4) {
+        let k = chunk.iter().rev().fold(0, |k, &x| (k << 8) | x as u32);
+        h ^= scramble(k);
+        if chunk.len() == 4 {
+            h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+        }
+    }
+
+    h ^= key.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^ (h >> 16)
+}
+
+fn fnv_atom(a: &[u8], init: u32) -> u32 {
     let mut c = init;
     for i in a.iter() {
         c = _fnv(*i as u32 ^ c);
@@ -341,17 +549,17 @@ fn mug_atom(a: &[u8], init: u32) -> u32 {
     if ret != 0 {
         ret
     } else {
-        mug_atom(a, init.wrapping_add(1))
+        fnv_atom(a, init.wrapping_add(1))
     }
 }
 
-fn mug_pair(p: u32, q: u32) -> u32 {
+fn fnv_pair(p: u32, q: u32) -> u32 {
     let c = _fnv(p ^ _fnv(q));
     let ret = (c >> 31) ^ (c & 0x7fffffff);
     if ret != 0 {
         ret
     } else {
-        mug_pair(p, q.wrapping_add(1))
+        fnv_pair(p, q.wrapping_add(1))
     }
 }
 
@@ -365,14 +573,6 @@ pub trait ToNoun {
     fn to_noun(&self) -> Noun;
 }
 
-impl<T> ToNoun for T
-    where T: DigitSlice
-{
-    fn to_noun(&self) -> Noun {
-        Noun::atom(self.as_digits())
-    }
-}
-
 /// A trait for types that can be instantiated from a Nock noun.
 pub trait FromNoun: Sized {
     /// Try to convert a noun to an instance of the type.
@@ -391,18 +591,11 @@ impl ToNoun for Noun {
     }
 }
 
-impl FromNoun for Rc<Vec<u8>> {
-    fn from_noun(n: &Noun) -> Result<Self, NockError> {
-        match n.value {
-            Inner::Atom(ref v) => Ok(v.clone()),
-            _ => Err(NockError("FromNoun Rc<Vec<u8>> not an atom".to_owned())),
-        }
-    }
-}
-
-impl ToNoun for Vec<u8> {
+impl<T> ToNoun for T
+    where T: DigitSlice
+{
     fn to_noun(&self) -> Noun {
-        Noun::atom(&self[..])
+        Noun::atom(self.as_digits())
     }
 }
 
@@ -412,14 +605,72 @@ impl<T> FromNoun for T
     fn from_noun(n: &Noun) -> Result<Self, NockError> {
         match n.get() {
             Shape::Atom(x) => {
-                T::from_digits(x)
-                    .map_err(|_| NockError("FromNoun FromDigits".to_owned()))
+                T::from_digits(x).map_err(|_| NockError::conversion::<T>(n))
             }
-            _ => Err(NockError("FromNoun FromDigits not an atom".to_owned())),
+            _ => Err(NockError::conversion::<T>(n)),
         }
     }
 }
 
+// `Box` is `#[fundamental]`, so a generic `Box<T>` impl would overlap the
+// `DigitSlice` and `FromDigits` impls above. Boxes of other types can get
+// their own impls in the crate that defines the type.
+macro_rules! pointer_impls {
+    ($($p:ident)::+) => {
+        impl FromNoun for $($p)::+<Noun> {
+            fn from_noun(n: &Noun) -> Result<Self, NockError> {
+                Ok($($p)::+::new(n.clone()))
+            }
+        }
+
+        impl FromNoun for $($p)::+<str> {
+            fn from_noun(n: &Noun) -> Result<Self, NockError> {
+                String::from_noun(n).map(Into::into)
+            }
+        }
+
+        impl<T: FromNoun> FromNoun for $($p)::+<[T]> {
+            fn from_noun(n: &Noun) -> Result<Self, NockError> {
+                Vec::from_noun(n).map(Into::into)
+            }
+        }
+    }
+}
+
+pointer_impls!(Box);
+pointer_impls!(std::rc::Rc);
+pointer_impls!(std::sync::Arc);
+
+impl ToNoun for Box<Noun> {
+    fn to_noun(&self) -> Noun {
+        (**self).clone()
+    }
+}
+
+impl ToNoun for Box<str> {
+    fn to_noun(&self) -> Noun {
+        (**self).to_noun()
+    }
+}
+
+impl<T: ToNoun> ToNoun for Box<[T]> {
+    fn to_noun(&self) -> Noun {
+        (**self).to_noun()
+    }
+}
+
+impl<T: ToNoun + ?Sized> ToNoun for std::rc::Rc<T> {
+    fn to_noun(&self) -> Noun {
+        (**self).to_noun()
+    }
+}
+
+impl<T: ToNoun + ?Sized> ToNoun for std::sync::Arc<T> {
+    fn to_noun(&self) -> Noun {
+        (**self).to_noun()
+    }
+}
+
 impl<T> FromNoun for (T,)
     where T: FromNoun
 {
@@ -436,58 +687,53 @@ impl<T> ToNoun for (T,)
     }
 }
 
-impl<T, U> FromNoun for (T, U)
-    where T: FromNoun,
-          U: FromNoun
-{
-    fn from_noun(n: &Noun) -> Result<Self, NockError> {
-        match n.get() {
-            Shape::Cell(a, b) => {
-                let t = try!(T::from_noun(a));
-                let u = try!(U::from_noun(b));
-                Ok((t, u))
+// Tuples are right-nested cells, so `(a, b, c)` is `[a b c]`.
+macro_rules! tuple_impls {
+    ($(($($t:ident),+; $last:ident))+) => {
+        $(
+        impl<$($t: ToNoun,)+ $last: ToNoun> ToNoun for ($($t,)+ $last) {
+            #[allow(non_snake_case)]
+            fn to_noun(&self) -> Noun {
+                let ($($t,)+ $last) = self;
+                vec![$($t.to_noun(),)+ $last.to_noun()].into_iter().collect()
             }
-            _ => Err(NockError("FromNoun (T, U) not a cell".to_owned())),
         }
-    }
-}
-
-impl<T, U> ToNoun for (T, U)
-    where T: ToNoun,
-          U: ToNoun
-{
-    fn to_noun(&self) -> Noun {
-        Noun::cell(self.0.to_noun(), self.1.to_noun())
-    }
-}
 
-impl<T1, T2, T3> FromNoun for (T1, T2, T3)
-    where T1: FromNoun,
-          T2: FromNoun,
-          T3: FromNoun
-{
-    fn from_noun(n: &Noun) -> Result<Self, NockError> {
-        match n.get_122() {
-            Some((t1, t2, t3)) => {
-                let t1 = try!(T1::from_noun(t1));
-                let t2 = try!(T2::from_noun(t2));
-                let t3 = try!(T3::from_noun(t3));
-                Ok((t1, t2, t3))
+        impl<$($t: FromNoun,)+ $last: FromNoun> FromNoun
+            for ($($t,)+ $last)
+        {
+            #[allow(non_snake_case)]
+            fn from_noun(n: &Noun) -> Result<Self, NockError> {
+                let mut rest = n;
+                $(
+                let $t = match rest.get() {
+                    Shape::Cell(a, b) => {
+                        rest = b;
+                        <$t as FromNoun>::from_noun(a)?
+                    }
+                    _ => return Err(NockError::conversion::<Self>(n)),
+                };
+                )+
+                let $last = <$last as FromNoun>::from_noun(rest)?;
+                Ok(($($t,)+ $last))
             }
-            _ => Err(NockError("FromNoun (T, U, V) not a tuple".to_owned())),
         }
+        )+
     }
 }
 
-impl<T1, T2, T3> ToNoun for (T1, T2, T3)
-    where T1: ToNoun,
-          T2: ToNoun,
-          T3: ToNoun
-{
-    fn to_noun(&self) -> Noun {
-        Noun::cell(self.0.to_noun(),
-                   Noun::cell(self.1.to_noun(), self.2.to_noun()))
-    }
+tuple_impls! {
+    (A; B)
+    (A, B; C)
+    (A, B, C; D)
+    (A, B, C, D; E)
+    (A, B, C, D, E; F)
+    (A, B, C, D, E, F; G)
+    (A, B, C, D, E, F, G; H)
+    (A, B, C, D, E, F, G, H; I)
+    (A, B, C, D, E, F, G, H, I; J)
+    (A, B, C, D, E, F, G, H, I, J; K)
+    (A, B, C, D, E, F, G, H, I, J, K; L)
 }
 
 impl FromNoun for String {
@@ -495,9 +741,9 @@ impl FromNoun for String {
         match n.get() {
             Shape::Atom(bytes) => {
                 String::from_utf8(bytes.to_vec())
-                    .map_err(|_| NockError("FromNoun String".to_owned()))
+                    .map_err(|_| NockError::conversion::<String>(n))
             }
-            _ => Err(NockError("FromNoun String not an atom".to_owned())),
+            _ => Err(NockError::conversion::<String>(n)),
         }
     }
 }
@@ -539,34 +785,325 @@ impl<T: FromNoun> FromNoun for Vec<T> {
                 ret.push(try!(T::from_noun(head)));
                 n = tail;
             } else {
-                return Err(NockError("FromNoun Vec<T>".to_owned()));
+                return Err(NockError::conversion::<Self>(n));
             }
         }
     }
 }
 
-// TODO: HashMap conversion.
+impl<T: ToNoun> ToNoun for [T] {
+    fn to_noun(&self) -> Noun {
+        self.iter()
+            .rev()
+            .fold(Noun::from(0u32), |acc, x| Noun::cell(x.to_noun(), acc))
+    }
+}
 
-// TODO: FromNoun/ToNoun for signed numbers using the Urbit representation
-// convention.
+impl ToNoun for Vec<u8> {
+    fn to_noun(&self) -> Noun {
+        Noun::atom(&self[..])
+    }
+}
 
+impl FromNoun for Rc<Vec<u8>> {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        if let Inner::Atom(ref v) = n.value {
+            return Ok(v.clone());
+        }
+        match n.get() {
+            Shape::Atom(bytes) => Ok(Rc::new(bytes.to_vec())),
+            _ => Err(NockError::conversion::<Self>(n)),
+        }
+    }
+}
+
+impl<T: ToNoun, const N: usize> ToNoun for [T; N] {
+    fn to_noun(&self) -> Noun {
+        self[..].to_noun()
+    }
+}
 
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub struct NockError(pub String);
+impl<T: FromNoun, const N: usize> FromNoun for [T; N] {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        <[T; N]>::try_from(Vec::from_noun(n)?)
+            .map_err(|_| NockError::conversion::<Self>(n))
+    }
+}
+
+// Options are Hoon units, `~` or `[~ u]`.
+
+impl<T: ToNoun> ToNoun for Option<T> {
+    fn to_noun(&self) -> Noun {
+        match self {
+            None => Noun::from(0u32),
+            Some(x) => Noun::cell(Noun::from(0u32), x.to_noun()),
+        }
+    }
+}
+
+impl<T: FromNoun> FromNoun for Option<T> {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        match n.get() {
+            Shape::Atom(_) if n.as_u32() == Some(0) => Ok(None),
+            Shape::Cell(a, b) if a.as_u32() == Some(0) => {
+                T::from_noun(b).map(Some)
+            }
+            _ => Err(NockError::conversion::<Self>(n)),
+        }
+    }
+}
+
+// Results are Hoon `each`, `[%.y p]` or `[%.n q]`.
+
+impl<T: ToNoun, E: ToNoun> ToNoun for Result<T, E> {
+    fn to_noun(&self) -> Noun {
+        match self {
+            Ok(x) => Noun::cell(true.to_noun(), x.to_noun()),
+            Err(e) => Noun::cell(false.to_noun(), e.to_noun()),
+        }
+    }
+}
+
+impl<T: FromNoun, E: FromNoun> FromNoun for Result<T, E> {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        match n.get() {
+            Shape::Cell(a, b) if a.as_u32() == Some(0) => {
+                T::from_noun(b).map(Ok)
+            }
+            Shape::Cell(a, b) if a.as_u32() == Some(1) => {
+                E::from_noun(b).map(Err)
+            }
+            _ => Err(NockError::conversion::<Self>(n)),
+        }
+    }
+}
+
+// Signed numbers use the Urbit `@s` representation, where non-negative
+// numbers are doubled and negative numbers are doubled and decremented, so
+// `--5` is 10 and `-5` is 9.
+
+macro_rules! signed_impls {
+    ($($t:ty),*) => {
+        $(
+        impl ToNoun for $t {
+            fn to_noun(&self) -> Noun {
+                let x = *self as i64;
+                Noun::from(((x << 1) ^ (x >> 63)) as u64)
+            }
+        }
+
+        impl FromNoun for $t {
+            fn from_noun(n: &Noun) -> Result<Self, NockError> {
+                let x = u64::from_noun(n)
+                            .map_err(|_| NockError::conversion::<$t>(n))?;
+                let x = (x >> 1) as i64 ^ -((x & 1) as i64);
+                <$t>::try_from(x).map_err(|_| NockError::conversion::<$t>(n))
+            }
+        }
+        )*
+    }
+}
+
+signed_impls!(i8, i16, i32, i64, isize);
+
+impl ToNoun for BigInt {
+    fn to_noun(&self) -> Noun {
+        match self.to_biguint() {
+            Some(x) => Noun::from(x << 1),
+            None => {
+                let x = (-self).to_biguint().unwrap();
+                Noun::from((x << 1) - BigUint::from(1u32))
+            }
+        }
+    }
+}
+
+impl FromNoun for BigInt {
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        let x = BigUint::from_noun(n)
+                    .map_err(|_| NockError::conversion::<BigInt>(n))?;
+        if x.is_even() {
+            Ok(BigInt::from(x >> 1))
+        } else {
+            Ok(-BigInt::from((x + BigUint::from(1u32)) >> 1))
+        }
+    }
+}
+
+
+/// Failure of a Nock computation or a noun conversion.
+///
+/// The nouns an error holds can be as large as a whole subject, so the
+/// `Display` and `Debug` output shows them by their mug.
+#[derive(Clone, PartialEq, Eq)]
+pub enum NockError {
+    /// Axis into an atom, or the invalid axis 0.
+    Axis { axis: Noun, subject: Noun },
+    /// Increment of a cell.
+    Bump(Noun),
+    /// Equality test on an atom.
+    Same(Noun),
+    /// Branch on a value other than 0 or 1.
+    If(Noun),
+    /// Formula with an unknown opcode or malformed arguments.
+    Opcode(Noun),
+    /// Noun that can not be converted to the target type.
+    Conversion { noun: Noun, target: &'static str },
+    /// Evaluation nested deeper than `Nock::max_depth` allows.
+    Exhausted,
+    /// Evaluation was stopped before it finished, such as by running out of
+    /// fuel.
+    ///
+    /// Evaluating `*[subject formula]` will resume the computation.
+    Interrupted { subject: Noun, formula: Noun },
+    /// Failure raised by user code, such as a `Nock` hook.
+    Other(String),
+    /// Failure inside code marked with `%spot`, `%mean`, `%hunk` or `%lose`
+    /// hints.
+    ///
+    /// The trace holds a `[tag clue]` cell for each enclosing hint, innermost
+    /// first.
+    Traced {
+        error: Box<NockError>,
+        trace: Vec<Noun>,
+    },
+}
+
+impl NockError {
+    /// Build a conversion error for a noun that doesn't fit type `T`.
+    pub fn conversion<T: ?Sized>(noun: &Noun) -> NockError {
+        NockError::Conversion {
+            noun: noun.clone(),
+            target: any::type_name::<T>(),
+        }
+    }
+
+    /// Return whether this is a deterministic Nock crash.
+    ///
+    /// Exits happen when the Nock spec has no reduction for a formula, and
+    /// evaluating the same formula again will always exit the same way.
+    /// Other errors depend on the host, such as the resources it allows.
+    pub fn is_exit(&self) -> bool {
+        matches!(*self.root(),
+                 NockError::Axis { .. } | NockError::Bump(_) |
+                 NockError::Same(_) | NockError::If(_) |
+                 NockError::Opcode(_))
+    }
+
+    /// Return the error without its stack trace.
+    pub fn root(&self) -> &NockError {
+        match *self {
+            NockError::Traced { ref error, .. } => error,
+            _ => self,
+        }
+    }
+
+    /// Return the stack trace of the error, innermost entry first.
+    pub fn trace(&self) -> &[Noun] {
+        match *self {
+            NockError::Traced { ref trace, .. } => trace,
+            _ => &[],
+        }
+    }
+}
 
 impl fmt::Display for NockError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        match *self {
+            NockError::Axis { ref axis, ref subject } => {
+                write!(f, "bad axis {} in {:?}", axis, Mug(subject))
+            }
+            NockError::Bump(ref x) => {
+                write!(f, "increment of cell {:?}", Mug(x))
+            }
+            NockError::Same(ref x) => {
+                write!(f, "equality test on atom {:?}", Mug(x))
+            }
+            NockError::If(ref x) => {
+                write!(f, "branch on non-loobean {:?}", Mug(x))
+            }
+            NockError::Opcode(ref x) => write!(f, "bad formula {:?}", Mug(x)),
+            NockError::Conversion { ref noun, target } => {
+                write!(f, "{:?} is not a valid {}", Mug(noun), target)
+            }
+            NockError::Exhausted => write!(f, "evaluation too deep"),
+            NockError::Interrupted { .. } => {
+                write!(f, "evaluation interrupted")
+            }
+            NockError::Other(ref s) => write!(f, "{}", s),
+            NockError::Traced { ref error, ref trace } => {
+                write!(f, "{}", error)?;
+                for entry in trace {
+                    write!(f, "\n  {}", entry)?;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
-impl Error for NockError {
-    fn description(&self) -> &str {
-        &self.0[..]
+impl fmt::Debug for NockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NockError::Axis { ref axis, ref subject } => {
+                f.debug_struct("Axis")
+                 .field("axis", axis)
+                 .field("subject", &Mug(subject))
+                 .finish()
+            }
+            NockError::Bump(ref x) => {
+                f.debug_tuple("Bump").field(&Mug(x)).finish()
+            }
+            NockError::Same(ref x) => {
+                f.debug_tuple("Same").field(&Mug(x)).finish()
+            }
+            NockError::If(ref x) => {
+                f.debug_tuple("If").field(&Mug(x)).finish()
+            }
+            NockError::Opcode(ref x) => {
+                f.debug_tuple("Opcode").field(&Mug(x)).finish()
+            }
+            NockError::Conversion { ref noun, target } => {
+                f.debug_struct("Conversion")
+                 .field("noun", &Mug(noun))
+                 .field("target", &target)
+                 .finish()
+            }
+            NockError::Exhausted => f.write_str("Exhausted"),
+            NockError::Interrupted { ref subject, ref formula } => {
+                f.debug_struct("Interrupted")
+                 .field("subject", &Mug(subject))
+                 .field("formula", &Mug(formula))
+                 .finish()
+            }
+            NockError::Other(ref s) => {
+                f.debug_tuple("Other").field(s).finish()
+            }
+            NockError::Traced { ref error, ref trace } => {
+                f.debug_struct("Traced")
+                 .field("error", error)
+                 .field("trace", trace)
+                 .finish()
+            }
+        }
     }
+}
 
-    fn cause(&self) -> Option<&Error> {
-        None
+/// Shows a noun by its mug, for nouns too large to print.
+struct Mug<'a>(&'a Noun);
+
+impl<'a> fmt::Debug for Mug<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "noun {:#x}", self.0.mug())
+    }
+}
+
+impl Error for NockError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            NockError::Traced { ref error, .. } => Some(&**error),
+            _ => None,
+        }
     }
 }
 
@@ -708,12 +1245,20 @@ impl fmt::Debug for Noun {
 #[cfg(test)]
 mod tests {
     use std::hash;
-    use num::BigUint;
-    use super::{Nock, Noun, Shape, FromNoun, ToNoun};
+    use num::{BigInt, BigUint};
+    use super::{Nock, NockSpec, NockError, Noun, Shape, FromNoun, ToNoun,
+                Memo};
 
     struct VM;
     impl Nock for VM {}
 
+    struct LegacyVM;
+    impl Nock for LegacyVM {
+        fn spec(&self) -> NockSpec {
+            NockSpec::Nock5K
+        }
+    }
+
     /// Macro for noun literals.
     ///
     /// Rust n![1, 2, 3] corresponds to Nock [1 2 3]
@@ -737,8 +1282,8 @@ mod tests {
         assert_eq!(input.parse::<Noun>().ok().expect("Parsing failed"), output);
     }
 
-    fn produces(input: &str, output: &str) {
-        let (s, f) = match input.parse::<Noun>() {
+    fn split(input: &str) -> (Noun, Noun) {
+        match input.parse::<Noun>() {
             Err(_) => panic!("Parsing failed"),
             Ok(x) => {
                 if let Shape::Cell(ref s, ref f) = x.get() {
@@ -747,11 +1292,26 @@ mod tests {
                     panic!("Unnockable input")
                 }
             }
-        };
+        }
+    }
+
+    fn produces(input: &str, output: &str) {
+        let (s, f) = split(input);
         assert_eq!(format!("{}", VM.nock_on(s, f).ok().expect("Eval failed")),
                    output);
     }
 
+    fn produces_with<N: Nock>(vm: &mut N, input: &str, output: &str) {
+        let (s, f) = split(input);
+        assert_eq!(format!("{}", vm.nock_on(s, f).ok().expect("Eval failed")),
+                   output);
+    }
+
+    fn fails_with<N: Nock>(vm: &mut N, input: &str) {
+        let (s, f) = split(input);
+        assert!(vm.nock_on(s, f).is_err());
+    }
+
     fn hash<T: hash::Hash>(t: &T) -> u64 {
         use std::hash::Hasher;
         let mut s = hash::SipHasher::new();
@@ -852,6 +1412,100 @@ mod tests {
         produces("[18446744073709551616 4 0 1]", "18.446.744.073.709.551.617");
     }
 
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync() {
+        use std::sync::Arc;
+        use std::thread;
+
+        fn is_sync<T: Send + Sync>() {}
+        is_sync::<Noun>();
+        is_sync::<NockError>();
+        is_sync::<Memo>();
+        is_sync::<::jets::Dashboard>();
+
+        let (s, f) = split("[10.000 8 [1 0] 8 [1 6 [5 [0 7] 4 0 6] [0 6] 9 2 \
+                            [0 2] [4 0 6] 0 7] 9 2 0 1]");
+        let shared = Arc::new((s, f));
+        let workers: Vec<_> = (0..4)
+                                  .map(|_| {
+                                      let shared = shared.clone();
+                                      thread::spawn(move || {
+                                          VM.nock_on(shared.0.clone(),
+                                                     shared.1.clone())
+                                      })
+                                  })
+                                  .collect();
+        for w in workers {
+            assert_eq!(w.join().unwrap(), Ok(Noun::from(9_999u32)));
+        }
+    }
+
+    #[test]
+    fn test_direct() {
+        use super::Inner;
+
+        fn is_direct(n: &Noun) -> bool {
+            matches!(n.value, Inner::Direct(_))
+        }
+
+        assert!(is_direct(&Noun::from(0u32)));
+        assert!(is_direct(&Noun::from(9_223_372_036_854_775_807u64)));
+        assert!(!is_direct(&Noun::from(9_223_372_036_854_775_808u64)));
+        // Trailing zeros don't change the atom.
+        assert!(is_direct(&Noun::atom(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0])));
+        assert_eq!(Noun::atom(&[1, 0, 0]), Noun::from(1u32));
+        assert_eq!(Noun::atom(&[0, 0]), Noun::from(0u32));
+        assert!(matches!(Noun::atom(&[0, 0]).get(),
+                         Shape::Atom(x) if x.is_empty()));
+
+        produces("[9223372036854775806 4 0 1]",
+                 "9.223.372.036.854.775.807");
+        produces("[9223372036854775807 4 0 1]",
+                 "9.223.372.036.854.775.808");
+        produces("[[9223372036854775808 9223372036854775808] 5 [0 2] 0 3]",
+                 "0");
+        assert_eq!(Noun::from(4_294_967_296u64).as_u32(), None);
+        assert_eq!(Noun::from(4_294_967_295u64).as_u32(), Some(4_294_967_295));
+        assert_eq!(Noun::from(18_446_744_073_709_551_615u64).as_u64(),
+                   Some(18_446_744_073_709_551_615));
+        assert_eq!(Noun::atom(&[0, 0, 0, 0, 0, 0, 0, 0, 1]).as_u64(), None);
+
+        // Axes past 64 bits.
+        let mut axis = vec![0; 8];
+        axis.push(1);
+        let mut subject = Noun::from(42u32);
+        for _ in 0..64 {
+            subject = Noun::cell(subject, Noun::from(0u32));
+        }
+        assert_eq!(::get_axis(&Noun::atom(&axis), &subject),
+                   Ok(Noun::from(42u32)));
+    }
+
+    #[test]
+    fn test_eq() {
+        let a: Noun = "[1 [2 3] 18446744073709551616]".parse().unwrap();
+        let b: Noun = "[1 [2 3] 18446744073709551616]".parse().unwrap();
+        let c: Noun = "[1 [2 3] 18446744073709551617]".parse().unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a, a.clone());
+        assert!(a != c);
+        assert!(a != Noun::from(1u32));
+        assert!(Noun::from(0u32) != n![0, 0]);
+
+        // Deep nouns are compared without recursion.
+        let deep = |n: u32| {
+            let mut ret = Noun::from(0u32);
+            for i in 0..n {
+                ret = Noun::cell(ret, Noun::from(i));
+            }
+            ret
+        };
+        let (a, b, c) = (deep(100_000), deep(100_000), deep(99_999));
+        assert_eq!(a, b);
+        assert!(a != c);
+    }
+
     #[test]
     fn test_same() {
         // Operator 5: Same
@@ -889,11 +1543,13 @@ mod tests {
 
         // Operator 9: Call
 
-        // Operator 10: Hint
-        produces("[[1 2] [10 [3 [0 1]] [0 1]]]", "[1 [1  2]]");
-        produces("[[1 2 3 4] [10 [6 [1 [123 456]]] [0 1]]]", "[ 1 [[ 123  456 ][ 3  4 ]]]");
+        // Operator 10: Edit
+        produces("[[1 2] [10 [3 [0 1]] [0 1]]]", "[1 1 2]");
+        produces("[[1 2 3 4] [10 [6 [1 [123 456]]] [0 1]]]",
+                 "[1 [123 456] 3 4]");
+        produces("[[1 2] [10 [1 [1 42]] [0 1]]]", "42");
 
-        // Operator 11
+        // Operator 11: Hint
         produces("[[132 19] [11 37 [4 0 3]]]", "20");
         produces("[[132 19] [11 [1 1 1] [4 0 3]]]", "20");
 
@@ -906,6 +1562,37 @@ mod tests {
                  "55");
     }
 
+    #[test]
+    fn test_spec() {
+        // Opcodes 6 to 9 behave the same in both versions.
+        for &spec in &[NockSpec::Nock4K, NockSpec::Nock5K] {
+            let (s, f) = split("[42 6 [1 0] [4 0 1] 1 233]");
+            assert_eq!(VM.nock_on_with(spec, s, f), Ok(Noun::from(43u32)));
+            let (s, f) = split("[42 8 [4 0 1] 4 0 3]");
+            assert_eq!(VM.nock_on_with(spec, s, f), Ok(Noun::from(43u32)));
+            let (s, f) = split("[[[4 0 3] 42] 9 2 0 1]");
+            assert_eq!(VM.nock_on_with(spec, s, f), Ok(Noun::from(43u32)));
+        }
+
+        // Nock 4K: 10 is edit, 11 is hint.
+        produces_with(&mut VM, "[[1 2] 10 [2 1 3] 0 1]", "[3 2]");
+        produces_with(&mut VM, "[[132 19] 11 37 4 0 3]", "20");
+        produces_with(&mut VM, "[[132 19] 11 [1 1 1] 4 0 3]", "20");
+        fails_with(&mut VM, "[[132 19] 11 [1 0 0] 4 0 3]");
+
+        // Nock 5K: 10 is hint, 11 is not defined.
+        produces_with(&mut LegacyVM, "[[132 19] 10 37 4 0 3]", "20");
+        produces_with(&mut LegacyVM, "[[132 19] 10 [1 1 1] 4 0 3]", "20");
+        produces_with(&mut LegacyVM, "[[1 2] 10 [2 1 3] 0 1]", "[1 2]");
+        fails_with(&mut LegacyVM, "[[132 19] 10 [1 0 0] 4 0 3]");
+        fails_with(&mut LegacyVM, "[[132 19] 11 37 4 0 3]");
+
+        // Explicit spec overrides the VM default.
+        let (s, f) = split("[[1 2] 10 [2 1 3] 0 1]");
+        assert_eq!(LegacyVM.nock_on_with(NockSpec::Nock4K, s, f),
+                   Ok(n![3, 2]));
+    }
+
     #[test]
     fn test_stack() {
         // Subtraction. Tests tail call elimination, will trash stack if it
@@ -915,6 +1602,284 @@ mod tests {
                  "9.999");
     }
 
+    #[test]
+    fn test_deep() {
+        struct ShallowVM;
+        impl Nock for ShallowVM {
+            fn max_depth(&self) -> usize {
+                1000
+            }
+        }
+
+        // Non-tail recursion far deeper than the native stack would allow.
+        // Counts from 0 up to the subject and bumps the result on the way
+        // back out.
+        let formula: Noun = "[8 [1 6 [5 [0 6] 0 7] [1 0] 4 9 2 [0 2] [4 0 6] \
+                             0 7] 9 2 [0 2] [1 0] 0 3]"
+                                .parse()
+                                .unwrap();
+        assert_eq!(VM.nock_on(Noun::from(100_000u32), formula.clone()),
+                   Ok(Noun::from(100_000u32)));
+        assert!(ShallowVM.nock_on(Noun::from(100_000u32), formula).is_err());
+
+        // The same recursion building a result that is just as deep, which
+        // can be folded, printed and dropped.
+        let formula: Noun = "[8 [1 6 [5 [0 6] 0 7] [1 0] [9 2 [0 2] [4 0 6] \
+                             0 7] 1 0] 9 2 [0 2] [1 0] 0 3]"
+                                .parse()
+                                .unwrap();
+        let deep = VM.nock_on(Noun::from(100_000u32), formula).unwrap();
+        let depth = deep.fold(|x: Shape<&[u8], usize>| match x {
+            Shape::Atom(_) => 0,
+            Shape::Cell(a, b) => 1 + a.max(b),
+        });
+        assert_eq!(depth, 100_000);
+        let (open, close) = ("[".repeat(100_000), " 0]".repeat(100_000));
+        assert_eq!(format!("{}", deep), format!("{}0{}", open, close));
+        drop(deep);
+    }
+
+    /// Evaluate in metered steps, resuming whenever the fuel runs out.
+    fn metered(input: &str, fuel_per_step: u64) -> (Noun, usize) {
+        let (mut s, mut f) = split(input);
+        let mut steps = 0;
+        loop {
+            let mut fuel = fuel_per_step;
+            match VM.nock_on_metered(s, f, &mut fuel) {
+                Ok(x) => return (x, steps),
+                Err(NockError::Interrupted { subject, formula }) => {
+                    s = subject;
+                    f = formula;
+                    steps += 1;
+                    assert!(steps < 10_000, "Metered evaluation stuck");
+                }
+                Err(e) => panic!("Eval failed: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_metered() {
+        let (s, f) = split("[57 4 0 1]");
+        let mut fuel = 10;
+        assert_eq!(VM.nock_on_metered(s.clone(), f.clone(), &mut fuel),
+                   Ok(Noun::from(58u32)));
+        assert_eq!(fuel, 8);
+
+        let mut fuel = 0;
+        assert_eq!(VM.nock_on_metered(s.clone(), f.clone(), &mut fuel),
+                   Err(NockError::Interrupted {
+                       subject: s,
+                       formula: f,
+                   }));
+
+        let (s, f) = split("[42 6 [1 2] [4 0 1] 1 233]");
+        assert_eq!(VM.nock_on_metered(s, f, &mut 100),
+                   Err(NockError::If(Noun::from(2u32))));
+
+        // Resuming suspended computations gives the same results as
+        // evaluating them in one go.
+        let programs = ["[[1 2 3 4] [10 [6 [1 [123 456]]] [0 1]]]",
+                        "[[132 19] [11 [1 1 1] [4 0 3]]]",
+                        "[77 [2 [1 42] [1 1 153 218]]]",
+                        "[[42 44] [7 [4 0 3] [3 0 1]]]",
+                        "[42 [8 [4 0 1] [4 0 3]]]",
+                        "[[1 2] 5 0 1]",
+                        "[20 8 [1 0] 8 [1 6 [5 [0 7] 4 0 6] [0 6] 9 2 [0 2] \
+                         [4 0 6] 0 7] 9 2 0 1]",
+                        "[10 8 [1 1 1] 8 [1 0] 8 [1 6 [5 [0 15] 4 0 6] [0 28] \
+                         9 2 [0 2] [4 0 6] [[0 29] 7 [0 14] 8 [1 0] 8 [1 6 \
+                         [5 [0 14] 0 6] [0 15] 9 2 [0 2] [4 0 6] [0 14] 4 0 \
+                         15] 9 2 0 1] 0 15] 9 2 0 1]"];
+        for p in programs.iter() {
+            let (s, f) = split(p);
+            let mut fuel = u64::MAX;
+            let expected = VM.nock_on_metered(s.clone(), f.clone(), &mut fuel)
+                             .unwrap();
+            let total = u64::MAX - fuel;
+
+            // Suspend at every possible point.
+            for n in 0..total {
+                match VM.nock_on_metered(s.clone(), f.clone(), &mut { n }) {
+                    Err(NockError::Interrupted { subject, formula }) => {
+                        assert_eq!(VM.nock_on(subject, formula),
+                                   Ok(expected.clone()));
+                    }
+                    x => panic!("Unexpected {:?}", x),
+                }
+            }
+        }
+
+        // Suspend a long computation many times over.
+        let (x, steps) = metered(programs[7], 100);
+        assert_eq!(x, Noun::from(55u32));
+        assert!(steps > 10);
+    }
+
+    #[test]
+    fn test_errors() {
+        fn error(input: &str) -> NockError {
+            let (s, f) = split(input);
+            VM.nock_on(s, f).unwrap_err()
+        }
+
+        assert_eq!(error("[[1 2] 0 7]"),
+                   NockError::Axis {
+                       axis: Noun::from(7u32),
+                       subject: n![1, 2],
+                   });
+        assert_eq!(error("[[1 2] 0 0]"),
+                   NockError::Axis {
+                       axis: Noun::from(0u32),
+                       subject: n![1, 2],
+                   });
+        assert_eq!(error("[[1 2] 10 [6 1 3] 0 1]"),
+                   NockError::Axis {
+                       axis: Noun::from(6u32),
+                       subject: n![1, 2],
+                   });
+        assert_eq!(error("[[1 2] 4 0 1]"), NockError::Bump(n![1, 2]));
+        assert_eq!(error("[1 5 0 1]"), NockError::Same(Noun::from(1u32)));
+        assert_eq!(error("[3 6 [0 1] [1 0] 1 1]"),
+                   NockError::If(Noun::from(3u32)));
+        assert_eq!(error("[42 12 0 1]"), NockError::Opcode(n![12, 0, 1]));
+        assert_eq!(error("[42 2 1]"), NockError::Opcode(n![2, 1]));
+        assert_eq!(error("[42 [0 1] 2 1]"), NockError::Opcode(n![2, 1]));
+        assert_eq!(error("[42 1]"), NockError::Opcode(Noun::from(1u32)));
+        assert!(error("[42 1]").is_exit());
+
+        assert_eq!(u8::from_noun(&Noun::from(256u32)),
+                   Err(NockError::Conversion {
+                       noun: Noun::from(256u32),
+                       target: "u8",
+                   }));
+        assert!(!NockError::conversion::<u8>(&Noun::from(256u32)).is_exit());
+        assert!(!NockError::Exhausted.is_exit());
+
+        // Nouns are shown by mug, however large they are.
+        let mut subject = Noun::from(1u32);
+        for _ in 0..64 {
+            subject = Noun::cell(subject.clone(), subject);
+        }
+        let e = NockError::Axis {
+            axis: Noun::from(0u32),
+            subject: subject.clone(),
+        };
+        assert_eq!(format!("{}", e),
+                   format!("bad axis 0 in noun {:#x}", subject.mug()));
+        assert_eq!(format!("{:?}", e),
+                   format!("Axis {{ axis: 0, subject: noun {:#x} }}",
+                           subject.mug()));
+        assert!(format!("{:?}", NockError::conversion::<u8>(&subject))
+                    .len() < 80);
+    }
+
+    #[test]
+    fn test_trace() {
+        fn error(input: &str) -> NockError {
+            let (s, f) = split(input);
+            VM.nock_on(s, f).unwrap_err()
+        }
+        let spot = Noun::from(1953460339u32);
+        let mean = Noun::from(1851876717u32);
+
+        let e = error("[[1 2] 11 [1953460339 1 42] 11 [1851876717 0 2] 0 7]");
+        assert_eq!(e.trace(),
+                   &[Noun::cell(mean.clone(), Noun::from(1u32)),
+                     Noun::cell(spot.clone(), Noun::from(42u32))]);
+        assert_eq!(e.root(),
+                   &NockError::Axis {
+                       axis: Noun::from(7u32),
+                       subject: n![1, 2],
+                   });
+        assert!(e.is_exit());
+        assert_eq!(format!("{}", e),
+                   format!("bad axis 7 in noun {:#x}\n  [1.851.876.717 1]\n  \
+                            [1.953.460.339 42]",
+                           n![1, 2].mug()));
+
+        // Static hints and other tags are not traced.
+        assert!(error("[[1 2] 11 1953460339 0 7]").trace().is_empty());
+        assert!(error("[[1 2] 11 [1 1 42] 0 7]").trace().is_empty());
+        // The trace ends with the hinted computation.
+        assert!(error("[[1 2] [11 [1953460339 1 42] 0 2] 0 7]")
+                    .trace()
+                    .is_empty());
+
+        // Suspended evaluations keep their trace.
+        let input = "[[1 2] 11 [1953460339 1 42] [4 0 2] 11 [1851876717 0 2] \
+                     4 0 1]";
+        let expected = error(input);
+        assert_eq!(expected.root(), &NockError::Bump(n![1, 2]));
+        assert_eq!(expected.trace(),
+                   &[Noun::cell(mean, Noun::from(1u32)),
+                     Noun::cell(spot, Noun::from(42u32))]);
+        for i in 0..9 {
+            let (s, f) = split(input);
+            let mut fuel = i;
+            match VM.nock_on_metered(s, f, &mut fuel) {
+                Err(NockError::Interrupted { subject, formula }) => {
+                    assert_eq!(VM.nock_on(subject, formula).unwrap_err(),
+                               expected);
+                }
+                e => panic!("Not interrupted at {}: {:?}", i, e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_memo() {
+        struct MemoVM {
+            memo: Memo,
+        }
+        impl Nock for MemoVM {
+            fn memo(&mut self) -> Option<&mut Memo> {
+                Some(&mut self.memo)
+            }
+        }
+
+        let mut vm = MemoVM { memo: Memo::new(2) };
+        let (s, f) = split("[41 11 1869440365 4 0 1]");
+        assert_eq!(vm.nock_on(s.clone(), f.clone()), Ok(Noun::from(42u32)));
+        assert_eq!(vm.memo.len(), 1);
+        assert_eq!(vm.memo.get(NockSpec::Nock4K, &s, &n![4, 0, 1]),
+                   Some(&Noun::from(42u32)));
+
+        // Cached results are used instead of evaluating.
+        vm.memo.insert(NockSpec::Nock4K,
+                       Noun::from(1u32),
+                       n![4, 0, 1],
+                       Noun::from(99u32));
+        produces_with(&mut vm, "[1 11 1869440365 4 0 1]", "99");
+        // Dynamic hints are cached too.
+        produces_with(&mut vm, "[1 11 [1869440365 1 0] 4 0 1]", "99");
+        // Without the hint nothing is cached.
+        produces_with(&mut vm, "[1 4 0 1]", "2");
+
+        // The oldest entry is evicted when the cache is full.
+        produces_with(&mut vm, "[7 11 1869440365 4 0 1]", "8");
+        assert_eq!(vm.memo.len(), 2);
+        assert_eq!(vm.memo.get(NockSpec::Nock4K, &s, &n![4, 0, 1]), None);
+        assert_eq!(vm.memo.get(NockSpec::Nock4K, &Noun::from(7u32),
+                               &n![4, 0, 1]),
+                   Some(&Noun::from(8u32)));
+
+        // Results are only used under the Nock version they came from.
+        vm.memo.insert(NockSpec::Nock4K,
+                       Noun::from(1u32),
+                       n![4, 0, 1],
+                       Noun::from(99u32));
+        let (s5, f5) = split("[1 10 1869440365 4 0 1]");
+        assert_eq!(vm.nock_on_with(NockSpec::Nock5K, s5, f5),
+                   Ok(Noun::from(2u32)));
+        assert_eq!(vm.memo.get(NockSpec::Nock5K, &Noun::from(1u32),
+                               &n![4, 0, 1]),
+                   Some(&Noun::from(2u32)));
+
+        // A VM without a cache evaluates normally.
+        produces("[1 11 1869440365 4 0 1]", "2");
+    }
+
     #[test]
     fn test_cord() {
         assert_eq!(String::from_noun(&Noun::from(0u32)), Ok("".to_string()));
@@ -925,22 +1890,125 @@ mod tests {
                    Ok("quux".to_string()));
     }
 
+    #[test]
+    fn test_containers() {
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        fn round_trip<T>(x: T, n: &str)
+            where T: ToNoun + FromNoun + PartialEq + ::std::fmt::Debug
+        {
+            let n: Noun = n.parse().unwrap();
+            assert_eq!(x.to_noun(), n);
+            assert_eq!(T::from_noun(&n), Ok(x));
+        }
+
+        round_trip((1u8, 2u8), "[1 2]");
+        round_trip((1u8, (2u8, 3u8), 4u8), "[1 [2 3] 4]");
+        round_trip((1u8, 2u8, (3u8, 4u8)), "[1 2 3 4]");
+        round_trip((1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8, 11u8,
+                    12u8),
+                   "[1 2 3 4 5 6 7 8 9 10 11 12]");
+        assert!(<(u8, u8, u8)>::from_noun(&n![1, 2]).is_err());
+        assert!(<(u8, u8)>::from_noun(&n![1, 256]).is_err());
+
+        round_trip(Some(5u32), "[0 5]");
+        round_trip(None::<u32>, "0");
+        round_trip(Some(None::<u32>), "[0 0]");
+        assert!(Option::<u32>::from_noun(&n![1, 5]).is_err());
+        assert!(Option::<u32>::from_noun(&Noun::from(1u32)).is_err());
+
+        round_trip(Ok::<u32, u32>(5), "[0 5]");
+        round_trip(Err::<u32, u32>(5), "[1 5]");
+        assert!(Result::<u32, u32>::from_noun(&n![2, 5]).is_err());
+        assert!(Result::<u32, u32>::from_noun(&Noun::from(0u32)).is_err());
+
+        round_trip(Box::<[u32]>::from(vec![1, 2]), "[1 2 0]");
+        round_trip(Box::new(n![1, 2]), "[1 2]");
+        round_trip(Rc::<[(u32, u32)]>::from(vec![(1, 2)]), "[[1 2] 0]");
+        round_trip(Arc::<str>::from("foo"), "7.303.014");
+        assert_eq!(Rc::new((1u32, 2u32)).to_noun(), n![1, 2]);
+        assert_eq!(Arc::new(5u32).to_noun(), Noun::from(5u32));
+
+        round_trip([1u32, 2, 3], "[1 2 3 0]");
+        round_trip([0u32; 0], "0");
+        assert!(<[u32; 2]>::from_noun(&n![1, 2, 3, 0]).is_err());
+        assert_eq!([1u32, 2][..].to_noun(), n![1, 2, 0]);
+        assert_eq!(Vec::<u32>::from_noun(&[1u32, 2][..].to_noun()),
+                   Ok(vec![1, 2]));
+
+        // Byte vectors are atoms, byte slices are lists.
+        let bytes: &[u8] = &[1, 2, 255];
+        assert_eq!(bytes.to_vec().to_noun(), Noun::atom(bytes));
+        assert_eq!(::Rc::<Vec<u8>>::from_noun(&Noun::atom(bytes)),
+                   Ok(::Rc::new(bytes.to_vec())));
+        assert_eq!(::Rc::<Vec<u8>>::from_noun(&Noun::from(5u32)),
+                   Ok(::Rc::new(vec![5])));
+        assert!(::Rc::<Vec<u8>>::from_noun(&n![1, 2]).is_err());
+        assert_eq!(bytes.to_noun(), n![1, 2, 255, 0]);
+    }
+
+    #[test]
+    fn test_signed() {
+        // (new:si & 5), (new:si | 5) and so on.
+        assert_eq!(Noun::from(5i32), Noun::from(10u32));
+        assert_eq!(Noun::from(-5i32), Noun::from(9u32));
+        assert_eq!(Noun::from(0i8), Noun::from(0u32));
+        assert_eq!(Noun::from(-1i64), Noun::from(1u32));
+        assert_eq!(Noun::from(i64::MAX), Noun::from(u64::MAX - 1));
+        assert_eq!(Noun::from(i64::MIN), Noun::from(u64::MAX));
+
+        // (old:si 9) is [| 5].
+        assert_eq!(i32::from_noun(&Noun::from(9u32)), Ok(-5));
+        assert_eq!(i16::from_noun(&Noun::from(10u32)), Ok(5));
+        assert_eq!(isize::from_noun(&Noun::from(0u32)), Ok(0));
+        assert_eq!(i8::from_noun(&Noun::from(255u32)), Ok(-128));
+        assert!(i8::from_noun(&Noun::from(256u32)).is_err());
+        assert!(i64::from_noun(&n![1, 2]).is_err());
+        assert!(i64::from_noun(&Noun::from(BigUint::from(1u32) << 64))
+                    .is_err());
+        for i in -300..300i32 {
+            assert_eq!(i32::from_noun(&Noun::from(i)), Ok(i));
+            assert_eq!(BigInt::from_noun(&Noun::from(i)),
+                       Ok(BigInt::from(i)));
+        }
+
+        let big = BigInt::from(1) << 100;
+        assert_eq!(Noun::from(big.clone()),
+                   Noun::from(BigUint::from(1u32) << 101));
+        assert_eq!(BigInt::from_noun(&Noun::from(-big.clone())),
+                   Ok(-big.clone()));
+        assert_eq!(BigInt::from_noun(&Noun::from(big.clone())), Ok(big));
+    }
+
     #[test]
     fn test_mug() {
-        assert_eq!(Noun::from(0u32).mug(), 18_652_612);
-        assert_eq!(Noun::from(1u32).mug(), 67_918_732);
-        assert_eq!(Noun::from(126u32).mug(), 2_064_403_808);
-        assert_eq!(Noun::from(10_000u32).mug(), 178_152_889);
-        assert_eq!(Noun::from(10_001u32).mug(), 714_838_017);
-        assert_eq!("123.456.789.123.456.789".parse::<Noun>().unwrap().mug(),
+        assert_eq!(Noun::from(0u32).mug(), 0x79ff_04e8);
+        assert_eq!(Noun::from(1u32).mug(), 0x715c_2a60);
+        assert_eq!("Hello, world!".to_noun().mug(), 0x4d44_1035);
+        assert_eq!(n![0, 0].mug(), 0x192f_5588);
+        assert_eq!(n![0, "Hello, world!".to_noun()].mug(), 0x056c_adc8);
+        assert_eq!(n!["Hello, world!".to_noun(), 0].mug(), 0x6f29_47af);
+        assert_eq!(n![1, 2, 3].mug(), 0x3a81_1aec);
+
+        // Mugs of older Urbit versions.
+        assert_eq!(Noun::from(0u32).fnv_mug(), 18_652_612);
+        assert_eq!(Noun::from(1u32).fnv_mug(), 67_918_732);
+        assert_eq!(Noun::from(126u32).fnv_mug(), 2_064_403_808);
+        assert_eq!(Noun::from(10_000u32).fnv_mug(), 178_152_889);
+        assert_eq!(Noun::from(10_001u32).fnv_mug(), 714_838_017);
+        assert_eq!("123.456.789.123.456.789"
+                       .parse::<Noun>()
+                       .unwrap()
+                       .fnv_mug(),
                    322_093_503);
         assert_eq!("123.456.789.123.456.789.123.456.789"
                        .parse::<Noun>()
                        .unwrap()
-                       .mug(),
+                       .fnv_mug(),
                    61_582_623);
-        assert_eq!(n![1, 2, 3, 4, 5, 0].mug(), 1_067_931_605);
+        assert_eq!(n![1, 2, 3, 4, 5, 0].fnv_mug(), 1_067_931_605);
         // This would get mugged to zero without the nonzero check.
-        assert_eq!(Noun::from(2_048_341_237u32).mug(), 1_229_723_070);
+        assert_eq!(Noun::from(2_048_341_237u32).fnv_mug(), 1_229_723_070);
     }
 }
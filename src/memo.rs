@@ -0,0 +1,86 @@
+//! Result cache for computations under the `%memo` hint.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash;
+use fnv;
+use {Noun, NockSpec};
+
+/// Cache of `*[subject formula]` results for `%memo` hinted formulas.
+///
+/// Entries are found by the Nock version and the cached mugs of the subject
+/// and formula, so one cache can serve evaluations under either version.
+/// When the cache is full, the oldest entry is evicted to make room for a
+/// new one.
+pub struct Memo {
+    capacity: usize,
+    entries: HashMap<(NockSpec, Noun, Noun),
+                     Noun,
+                     hash::BuildHasherDefault<fnv::FnvHasher>>,
+    order: VecDeque<(NockSpec, Noun, Noun)>,
+}
+
+impl Memo {
+    /// Create a cache that holds at most `capacity` results.
+    pub fn new(capacity: usize) -> Memo {
+        Memo {
+            capacity,
+            entries: HashMap::default(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Look up the cached result of `*[subject formula]` under `spec`.
+    pub fn get(&self,
+               spec: NockSpec,
+               subject: &Noun,
+               formula: &Noun)
+               -> Option<&Noun> {
+        self.entries.get(&(spec, subject.clone(), formula.clone()))
+    }
+
+    /// Store the result of `*[subject formula]` under `spec`.
+    pub fn insert(&mut self,
+                  spec: NockSpec,
+                  subject: Noun,
+                  formula: Noun,
+                  value: Noun) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = (spec, subject, formula);
+        if self.entries.contains_key(&key) {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            match self.order.pop_front() {
+                Some(old) => {
+                    self.entries.remove(&old);
+                }
+                None => break,
+            }
+        }
+        self.order.push_back(key.clone());
+        self.entries.insert(key, value);
+    }
+
+    /// Maximum number of results kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of results currently cached.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no results are cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop all cached results.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
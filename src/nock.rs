@@ -1,6 +1,8 @@
 use num::BigUint;
 use num::traits::One;
-use digit_slice::{DigitSlice, FromDigits, msb};
+use digit_slice::{FromDigits, msb};
+use jets::Dashboard;
+use memo::Memo;
 use {Shape, Noun, NockError, NockResult};
 
 /// Interface for a virtual machine for Nock code.
@@ -23,9 +25,26 @@ pub trait Nock {
         None
     }
 
+    /// Jets for accelerating Call operations.
+    ///
+    /// If this returns a dashboard, `*[a 9 b c]` runs the dashboard's jet for
+    /// arm `b` of core `*[a c]` if there is one, before trying `call`. Cores
+    /// declared with the `%fast` hint are also registered with it.
+    ///
+    /// In checking mode, the arm is evaluated as well and the dashboard is
+    /// told about any difference from the jet's result.
+    fn jets(&mut self) -> Option<&mut Dashboard> {
+        None
+    }
+
     /// Handle a Nock hint.
     ///
-    /// Nock `*[a 10 b c]` will trigger `hint(a, b, c)`.
+    /// Nock `*[a 11 b c]` will trigger `hint(a, b, c)`. For a dynamic hint
+    /// `b = [p q]`, the clue formula `q` is evaluated before the hook runs.
+    ///
+    /// Dynamic `%spot`, `%mean`, `%hunk` and `%lose` hints are also recorded
+    /// by the evaluator, and their clues are attached to any error raised
+    /// while evaluating `c`.
     #[allow(unused_variables)]
     fn hint(&mut self,
             subject: &Noun,
@@ -35,211 +54,733 @@ pub trait Nock {
         Ok(())
     }
 
+    /// Cache for formulas under the `%memo` hint.
+    ///
+    /// If this returns a cache, `*[a 11 %memo c]` looks up `*[a c]` in it
+    /// before evaluating, and stores the result afterwards.
+    fn memo(&mut self) -> Option<&mut Memo> {
+        None
+    }
+
+    /// Nock specification version used by `nock_on`.
+    fn spec(&self) -> NockSpec {
+        NockSpec::Nock4K
+    }
+
+    /// Maximum number of pending computations the evaluator will keep.
+    ///
+    /// Nock that nests deeper than this fails with an error instead of
+    /// running out of memory.
+    fn max_depth(&self) -> usize {
+        DEFAULT_MAX_DEPTH
+    }
+
     /// Evaluate the nock `*[subject formula]`
-    fn nock_on(&mut self, mut subject: Noun, mut formula: Noun) -> NockResult {
-        loop {
-            if let Shape::Cell(ops, tail) = formula.clone().get() {
-                match ops.as_u32() {
-                    // Axis
-                    Some(0) => return get_axis(tail, &subject),
-
-                    // Just
-                    Some(1) => return Ok(tail.clone()),
-
-                    // Fire
-                    Some(2) => {
-                        match tail.get() {
-                            Shape::Cell(ref b, ref c) => {
-                                let p = try!(self.nock_on(subject.clone(),
-                                                          (*b).clone()));
-                                let q = try!(self.nock_on(subject,
-                                                          (*c).clone()));
-                                subject = p;
-                                formula = q;
-                                continue;
-                            }
-                            _ => return Err(NockError("fire".to_owned())),
-                        }
-                    }
+    fn nock_on(&mut self, subject: Noun, formula: Noun) -> NockResult {
+        let spec = self.spec();
+        self.nock_on_with(spec, subject, formula)
+    }
 
-                    // Depth
-                    Some(3) => {
-                        let p = try!(self.nock_on(subject.clone(),
-                                                  (*tail).clone()));
-                        return match p.get() {
-                            Shape::Cell(_, _) => Ok(Noun::from(0u32)),
-                            _ => Ok(Noun::from(1u32)),
-                        };
-                    }
+    /// Evaluate the nock `*[subject formula]` using a specific version of the
+    /// Nock specification.
+    fn nock_on_with(&mut self,
+                    spec: NockSpec,
+                    subject: Noun,
+                    formula: Noun)
+                    -> NockResult {
+        eval(self, spec, subject, formula, None)
+    }
 
-                    // Bump
-                    Some(4) => {
-                        let p = try!(self.nock_on(subject.clone(),
-                                                  (*tail).clone()));
-                        return match p.get() {
-                            Shape::Atom(ref x) => {
-                                // TODO: Non-bignum optimization
-                                Ok(Noun::from(BigUint::from_digits(x).unwrap() +
-                                              BigUint::one()))
-                            }
-                            _ => Err(NockError("bump".to_owned())),
-                        };
-                    }
+    /// Evaluate the nock `*[subject formula]` with a limited budget.
+    ///
+    /// Each opcode dispatch uses up one unit of fuel. If the fuel runs out
+    /// before the evaluation is done, the remaining computation is returned
+    /// in a `NockError::Interrupted` as a new subject and formula that can be
+    /// evaluated to resume it.
+    fn nock_on_metered(&mut self,
+                       subject: Noun,
+                       formula: Noun,
+                       fuel: &mut u64)
+                       -> NockResult {
+        let spec = self.spec();
+        eval(self, spec, subject, formula, Some(fuel))
+    }
+}
 
-                    // Same
-                    Some(5) => {
-                        let p = try!(self.nock_on(subject.clone(),
-                                                  (*tail).clone()));
-                        return match p.get() {
-                            Shape::Cell(ref a, ref b) => {
-                                if a == b {
-                                    // Yes.
-                                    return Ok(Noun::from(0u32));
-                                } else {
-                                    // No.
-                                    return Ok(Noun::from(1u32));
-                                }
-                            }
-                            _ => return Err(NockError("same".to_owned())),
-                        };
-                    }
+/// Default value for `Nock::max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 1 << 20;
 
-                    // If
-                    Some(6) => {
-                        if let Some((b, c, d)) = tail.get_122() {
-                            let p = try!(self.nock_on(subject.clone(),
-                                                      (*b).clone()));
-                            match p.get() {
-                                Shape::Atom(ref x) => {
-                                    if x == &0u32.as_digits() {
-                                        formula = (*c).clone();
-                                    } else if x == &1u32.as_digits() {
-                                        formula = (*d).clone();
-                                    } else {
-                                        return Err(NockError("if".to_owned()));
-                                    }
-                                }
-                                _ => return Err(NockError("if".to_owned())),
-                            }
-                            continue;
-                        } else {
-                            return Err(NockError("if".to_owned()));
-                        }
-                    }
+/// Version of the Nock specification to evaluate formulas with.
+///
+/// The versions share opcodes 0 to 9 and differ in what follows them.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum NockSpec {
+    /// Legacy Nock 5K, opcode 10 is a hint and there is no opcode 11.
+    Nock5K,
+    /// Current Nock 4K, opcode 10 is an edit and opcode 11 is a hint.
+    Nock4K,
+}
 
-                    // Compose
-                    Some(7) => {
-                        match tail.get() {
-                            Shape::Cell(ref b, ref c) => {
-                                let p = try!(self.nock_on(subject.clone(),
-                                                          (*b).clone()));
-                                subject = p;
-                                formula = (*c).clone();
-                                continue;
-                            }
-                            _ => return Err(NockError("compose".to_owned())),
-                        }
-                    }
+impl NockSpec {
+    fn hint_opcode(self) -> u32 {
+        match self {
+            NockSpec::Nock5K => 10,
+            NockSpec::Nock4K => 11,
+        }
+    }
+}
 
-                    // Push
-                    Some(8) => {
-                        match tail.get() {
-                            Shape::Cell(ref b, ref c) => {
-                                let p = try!(self.nock_on(subject.clone(),
-                                                          (*b).clone()));
-                                subject = Noun::cell(p, subject);
-                                formula = (*c).clone();
-                                continue;
-                            }
-                            _ => return Err(NockError("push".to_owned())),
-                        }
+/// Pending computation waiting for the value of a subformula.
+enum Frame {
+    /// Fire, evaluate formula c against the subject.
+    FireFormula { subject: Noun, c: Noun },
+    /// Fire, evaluate the computed formula against the computed subject.
+    Fire { subject: Noun },
+    Depth,
+    Bump,
+    Same,
+    If { subject: Noun, c: Noun, d: Noun },
+    Compose { c: Noun },
+    Push { subject: Noun, c: Noun },
+    Call { axis: Noun },
+    /// Edit, evaluate the target after the new value.
+    EditValue { subject: Noun, axis: Noun, d: Noun },
+    EditTarget { axis: Noun, value: Noun },
+    /// Dynamic hint whose clue has been computed.
+    Hint { subject: Noun, hint: Noun, c: Noun },
+    /// Autocons, evaluate the tail after the head.
+    Cons { subject: Noun, tail: Noun },
+    ConsTail { head: Noun },
+    /// Leave the scope of the innermost trace hint.
+    TracePop,
+    /// Cache the result of a `%memo` hinted formula.
+    MemoStore { subject: Noun, formula: Noun },
+    /// Declare the core produced under a `%fast` hint.
+    Declare { clue: Noun },
+    /// Compare an arm's value with the result of its jet.
+    JetCheck {
+        core: Noun,
+        axis: Noun,
+        result: NockResult,
+    },
+}
+
+/// Next step of the evaluation loop.
+enum Next {
+    /// Evaluate `*[subject formula]`.
+    Eval(Noun, Noun),
+    /// Pass a computed value to the topmost frame.
+    Return(Noun),
+}
+
+/// Run the Nock evaluation loop.
+///
+/// Pending computations are kept in a heap-allocated stack, so deeply nested
+/// formulas do not recurse on the native stack.
+fn eval<N: Nock + ?Sized>(vm: &mut N,
+                          spec: NockSpec,
+                          subject: Noun,
+                          formula: Noun,
+                          mut fuel: Option<&mut u64>)
+                          -> NockResult {
+    let max_depth = vm.max_depth();
+    let mut stack = Vec::new();
+    let mut trace = Vec::new();
+    let mut next = Next::Eval(subject, formula);
+
+    loop {
+        next = match next {
+            Next::Eval(subject, formula) => {
+                if let Some(ref mut fuel) = fuel {
+                    if **fuel == 0 {
+                        let (subject, formula) =
+                            suspend(spec, stack, trace, subject, formula);
+                        return Err(NockError::Interrupted {
+                            subject,
+                            formula,
+                        });
+                    }
+                    **fuel -= 1;
+                }
+                reduce(vm, spec, &mut stack, subject, formula)
+                    .map_err(|e| fail(vm, &stack, &trace, e))?
+            }
+            Next::Return(value) => {
+                match stack.pop() {
+                    Some(frame) => {
+                        resume(vm, spec, &mut stack, &mut trace, frame, value)
+                            .map_err(|e| fail(vm, &stack, &trace, e))?
                     }
+                    None => return Ok(value),
+                }
+            }
+        };
 
-                    // Call
-                    Some(9) => {
-                        match tail.get() {
-                            Shape::Cell(ref axis, ref c) => {
-                                // Construct core.
-                                subject = try!(self.nock_on(subject.clone(),
-                                                            (*c).clone()));
-                                // Fetch from core using axis.
-                                formula = try!(get_axis(axis, &subject));
-
-                                if let Some(result) = self.call(&subject,
-                                                                &formula) {
-                                    return result;
-                                }
-
-                                continue;
-                            }
-                            _ => return Err(NockError("call".to_owned())),
-                        }
+        if stack.len() > max_depth {
+            return Err(fail(vm, &stack, &trace, NockError::Exhausted));
+        }
+    }
+}
+
+/// The `%fast` hint tag as an atom.
+const FAST: u32 = 0x74736166;
+
+/// Hint tags whose clues are collected into the stack trace.
+const TRACE_TAGS: [&str; 4] = ["spot", "mean", "hunk", "lose"];
+
+fn is_trace_tag(tag: &Noun) -> bool {
+    TRACE_TAGS.iter().any(|t| has_tag(tag, t))
+}
+
+fn has_tag(tag: &Noun, name: &str) -> bool {
+    match tag.get() {
+        Shape::Atom(x) => name.as_bytes() == x,
+        _ => false,
+    }
+}
+
+/// Build the error for an evaluation that failed with the pending frames on
+/// the stack.
+fn fail<N: Nock + ?Sized>(vm: &mut N,
+                          stack: &[Frame],
+                          trace: &[Noun],
+                          error: NockError)
+                          -> NockError {
+    if error.is_exit() {
+        if let Some(jets) = vm.jets() {
+            // Arms being checked against their jets crashed along with the
+            // evaluation.
+            let nock = Err(error.clone());
+            for frame in stack {
+                if let Frame::JetCheck { core, axis, result } = frame {
+                    jets.check(core, axis, result, &nock);
+                }
+            }
+        }
+    }
+    traced(trace, error)
+}
+
+/// Attach the active trace hints to an error.
+fn traced(trace: &[Noun], error: NockError) -> NockError {
+    if trace.is_empty() {
+        return error;
+    }
+    if let NockError::Interrupted { .. } = error {
+        // The resumable state already carries its trace hints.
+        return error;
+    }
+    let (error, mut inner) = match error {
+        NockError::Traced { error, trace } => (error, trace),
+        e => (Box::new(e), Vec::new()),
+    };
+    inner.extend(trace.iter().rev().cloned());
+    NockError::Traced {
+        error,
+        trace: inner,
+    }
+}
+
+/// Turn a suspended evaluation into a subject and formula that will finish
+/// it.
+///
+/// Each frame is wrapped around the current computation `*[s f]` as
+/// `*[[s d] 8 [2 [0 2] 1 f] g]`, where `d` is the data saved in the frame and
+/// `g` finishes the frame's work with the value at axis 2 and `d` at axis 7.
+/// Trace scopes and `%fast` declarations are restored by wrapping `f` in a
+/// dynamic hint with the saved clue as a constant, `[11 [tag 1 clue] f]`
+/// (`10` in Nock 5K).
+fn suspend(spec: NockSpec,
+           stack: Vec<Frame>,
+           mut trace: Vec<Noun>,
+           subject: Noun,
+           formula: Noun)
+           -> (Noun, Noun) {
+    fn f(formula: &str) -> Noun {
+        formula.parse().expect("Bad continuation formula")
+    }
+
+    stack.into_iter().rev().fold((subject, formula), |(s, f0), frame| {
+        let (d, g) = match frame {
+            // The result just doesn't get cached or checked.
+            Frame::MemoStore { .. } |
+            Frame::JetCheck { .. } => return (s, f0),
+            Frame::Declare { clue } => {
+                let hint = Noun::cell(Noun::from(FAST),
+                                      Noun::cell(Noun::from(1u32), clue));
+                let op = Noun::from(spec.hint_opcode());
+                return (s, Noun::cell(op, Noun::cell(hint, f0)));
+            }
+            Frame::TracePop => {
+                let entry = trace.pop().expect("Unbalanced trace");
+                let (tag, clue) = match entry.get() {
+                    Shape::Cell(tag, clue) => (tag.clone(), clue.clone()),
+                    _ => unreachable!(),
+                };
+                let hint = Noun::cell(tag, Noun::cell(Noun::from(1u32), clue));
+                let op = Noun::from(spec.hint_opcode());
+                return (s, Noun::cell(op, Noun::cell(hint, f0)));
+            }
+            Frame::FireFormula { subject, c } => {
+                (Noun::cell(subject, c), f("[2 [0 2] 2 [0 14] 0 15]"))
+            }
+            Frame::Fire { subject } => (subject, f("[2 [0 7] 0 2]")),
+            Frame::Depth => (Noun::from(0u32), f("[3 0 2]")),
+            Frame::Bump => (Noun::from(0u32), f("[4 0 2]")),
+            Frame::Same => (Noun::from(0u32), f("[5 0 2]")),
+            Frame::If { subject, c, d } => {
+                (Noun::cell(subject, Noun::cell(c, d)),
+                 f("[6 [0 2] [2 [0 14] 0 30] 2 [0 14] 0 31]"))
+            }
+            Frame::Compose { c } => (c, f("[2 [0 2] 0 7]")),
+            Frame::Push { subject, c } => {
+                (Noun::cell(subject, c), f("[2 [[0 2] 0 14] 0 15]"))
+            }
+            Frame::Call { axis } => (axis, f("[2 [0 2] [1 9] [0 7] 1 0 1]")),
+            Frame::EditValue { subject, axis, d } => {
+                (Noun::cell(subject, Noun::cell(axis, d)),
+                 f("[2 [0 14] [1 10] [[0 30] [1 1] 0 2] 0 31]"))
+            }
+            Frame::EditTarget { axis, value } => {
+                (Noun::cell(axis, value),
+                 f("[2 [0 2] [1 10] [[0 14] [1 1] 0 15] 1 0 1]"))
+            }
+            Frame::Hint { subject, hint, c } => {
+                let tag = match hint.get() {
+                    Shape::Cell(tag, _) => tag.clone(),
+                    _ => unreachable!(),
+                };
+                (Noun::cell(subject, Noun::cell(tag, c)),
+                 f(&format!("[2 [0 14] [1 {}] [[0 30] [1 1] 0 2] 0 31]",
+                            spec.hint_opcode())))
+            }
+            Frame::Cons { subject, tail } => {
+                (Noun::cell(subject, tail), f("[[0 2] 2 [0 14] 0 15]"))
+            }
+            Frame::ConsTail { head } => (head, f("[[0 7] 0 2]")),
+        };
+
+        let inner = Noun::cell(Noun::from(2u32),
+                               Noun::cell(f("[0 2]"),
+                                          Noun::cell(Noun::from(1u32), f0)));
+        (Noun::cell(s, d),
+         Noun::cell(Noun::from(8u32), Noun::cell(inner, g)))
+    })
+}
+
+/// Dispatch a formula on its opcode.
+fn reduce<N: Nock + ?Sized>(vm: &mut N,
+                            spec: NockSpec,
+                            stack: &mut Vec<Frame>,
+                            subject: Noun,
+                            formula: Noun)
+                            -> Result<Next, NockError> {
+    let (ops, tail) = match formula.get() {
+        Shape::Cell(ops, tail) => (ops, tail),
+        _ => return Err(NockError::Opcode(formula.clone())),
+    };
+
+    match ops.as_u32() {
+        // Axis
+        Some(0) => Ok(Next::Return(get_axis(tail, &subject)?)),
+
+        // Just
+        Some(1) => Ok(Next::Return(tail.clone())),
+
+        // Fire
+        Some(2) => {
+            match tail.get() {
+                Shape::Cell(b, c) => {
+                    stack.push(Frame::FireFormula {
+                        subject: subject.clone(),
+                        c: c.clone(),
+                    });
+                    Ok(Next::Eval(subject, b.clone()))
+                }
+                _ => Err(NockError::Opcode(formula.clone())),
+            }
+        }
+
+        // Depth
+        Some(3) => {
+            stack.push(Frame::Depth);
+            Ok(Next::Eval(subject, tail.clone()))
+        }
+
+        // Bump
+        Some(4) => {
+            stack.push(Frame::Bump);
+            Ok(Next::Eval(subject, tail.clone()))
+        }
+
+        // Same
+        Some(5) => {
+            stack.push(Frame::Same);
+            Ok(Next::Eval(subject, tail.clone()))
+        }
+
+        // If
+        Some(6) => {
+            match tail.get_122() {
+                Some((b, c, d)) => {
+                    stack.push(Frame::If {
+                        subject: subject.clone(),
+                        c: c.clone(),
+                        d: d.clone(),
+                    });
+                    Ok(Next::Eval(subject, b.clone()))
+                }
+                None => Err(NockError::Opcode(formula.clone())),
+            }
+        }
+
+        // Compose
+        Some(7) => {
+            match tail.get() {
+                Shape::Cell(b, c) => {
+                    stack.push(Frame::Compose { c: c.clone() });
+                    Ok(Next::Eval(subject, b.clone()))
+                }
+                _ => Err(NockError::Opcode(formula.clone())),
+            }
+        }
+
+        // Push
+        Some(8) => {
+            match tail.get() {
+                Shape::Cell(b, c) => {
+                    stack.push(Frame::Push {
+                        subject: subject.clone(),
+                        c: c.clone(),
+                    });
+                    Ok(Next::Eval(subject, b.clone()))
+                }
+                _ => Err(NockError::Opcode(formula.clone())),
+            }
+        }
+
+        // Call
+        Some(9) => {
+            match tail.get() {
+                Shape::Cell(axis, c) => {
+                    stack.push(Frame::Call { axis: axis.clone() });
+                    Ok(Next::Eval(subject, c.clone()))
+                }
+                _ => Err(NockError::Opcode(formula.clone())),
+            }
+        }
+
+        // Hint
+        Some(op) if op == spec.hint_opcode() => {
+            match tail.get() {
+                Shape::Cell(b, c) => {
+                    if let Shape::Cell(_, clue) = b.get() {
+                        // Dynamic hints must still crash if the clue formula
+                        // does.
+                        stack.push(Frame::Hint {
+                            subject: subject.clone(),
+                            hint: b.clone(),
+                            c: c.clone(),
+                        });
+                        return Ok(Next::Eval(subject, clue.clone()));
                     }
+                    vm.hint(&subject, b, c)?;
+                    Ok(hinted(vm, spec, stack, subject, b, c.clone()))
+                }
+                _ => Err(NockError::Opcode(formula.clone())),
+            }
+        }
+
+        // Edit
+        Some(10) if spec == NockSpec::Nock4K => {
+            if let Shape::Cell(hint, d) = tail.get() {
+                if let Shape::Cell(b, c) = hint.get() {
+                    stack.push(Frame::EditValue {
+                        subject: subject.clone(),
+                        axis: b.clone(),
+                        d: d.clone(),
+                    });
+                    return Ok(Next::Eval(subject, c.clone()));
+                }
+            }
+            Err(NockError::Opcode(formula.clone()))
+        }
 
-                    // Hint
-                    Some(10) => {
-                        match tail.get() {
-                            Shape::Cell(ref b, ref c) => {
-                                try!(self.hint(&subject, b, c));
-                                formula = (*c).clone();
-                                continue;
-                            }
-                            _ => return Err(NockError("hint".to_owned())),
+        // Unhandled opcode
+        Some(_) => Err(NockError::Opcode(formula.clone())),
+
+        None => {
+            if let Shape::Cell(_, _) = ops.get() {
+                // Autocons
+                stack.push(Frame::Cons {
+                    subject: subject.clone(),
+                    tail: tail.clone(),
+                });
+                Ok(Next::Eval(subject, ops.clone()))
+            } else {
+                Err(NockError::Opcode(formula.clone()))
+            }
+        }
+    }
+}
+
+/// Continue a pending computation with the value it was waiting for.
+fn resume<N: Nock + ?Sized>(vm: &mut N,
+                            spec: NockSpec,
+                            stack: &mut Vec<Frame>,
+                            trace: &mut Vec<Noun>,
+                            frame: Frame,
+                            value: Noun)
+                            -> Result<Next, NockError> {
+    match frame {
+        Frame::FireFormula { subject, c } => {
+            stack.push(Frame::Fire { subject: value });
+            Ok(Next::Eval(subject, c))
+        }
+
+        Frame::Fire { subject } => Ok(Next::Eval(subject, value)),
+
+        Frame::Depth => {
+            match value.get() {
+                Shape::Cell(_, _) => Ok(Next::Return(Noun::from(0u32))),
+                _ => Ok(Next::Return(Noun::from(1u32))),
+            }
+        }
+
+        Frame::Bump => {
+            match value.get() {
+                Shape::Atom(x) => {
+                    match value.as_u64() {
+                        Some(n) if n < u64::MAX => {
+                            Ok(Next::Return(Noun::from(n + 1)))
+                        }
+                        _ => {
+                            Ok(Next::Return(Noun::from(BigUint::from_digits(x)
+                                                           .unwrap() +
+                                                       BigUint::one())))
                         }
                     }
+                }
+                _ => Err(NockError::Bump(value.clone())),
+            }
+        }
 
-                    // Unhandled opcode
-                    Some(code) => {
-                        return Err(NockError(format!("unknown opcode {}",
-                                                     code)));
+        Frame::Same => {
+            match value.get() {
+                Shape::Cell(a, b) => {
+                    if a == b {
+                        // Yes.
+                        Ok(Next::Return(Noun::from(0u32)))
+                    } else {
+                        // No.
+                        Ok(Next::Return(Noun::from(1u32)))
                     }
+                }
+                _ => Err(NockError::Same(value.clone())),
+            }
+        }
 
-                    None => {
-                        if let Shape::Cell(_, _) = ops.get() {
-                            // Autocons
-                            let a = try!(self.nock_on(subject.clone(),
-                                                      ops.clone()));
-                            let b = try!(self.nock_on(subject, tail.clone()));
-                            return Ok(Noun::cell(a, b));
-                        } else {
-                            return Err(NockError("autocons".to_owned()));
-                        }
+        Frame::If { subject, c, d } => {
+            match value.as_u32() {
+                Some(0) => Ok(Next::Eval(subject, c)),
+                Some(1) => Ok(Next::Eval(subject, d)),
+                _ => Err(NockError::If(value.clone())),
+            }
+        }
+
+        Frame::Compose { c } => Ok(Next::Eval(value, c)),
+
+        Frame::Push { subject, c } => {
+            Ok(Next::Eval(Noun::cell(value, subject), c))
+        }
+
+        Frame::Call { axis } => {
+            // Fetch arm from the core using axis.
+            let formula = get_axis(&axis, &value)?;
+
+            if let Some(jets) = vm.jets() {
+                if let Some(result) = jets.call(&value, &axis) {
+                    if !jets.checking() {
+                        return Ok(Next::Return(result?));
                     }
+                    stack.push(Frame::JetCheck {
+                        core: value.clone(),
+                        axis,
+                        result,
+                    });
+                    return Ok(Next::Eval(value, formula));
                 }
-            } else {
-                return Err(NockError("nock".to_owned()));
             }
+
+            if let Some(result) = vm.call(&value, &formula) {
+                return Ok(Next::Return(result?));
+            }
+
+            Ok(Next::Eval(value, formula))
+        }
+
+        Frame::EditValue { subject, axis, d } => {
+            stack.push(Frame::EditTarget { axis, value });
+            Ok(Next::Eval(subject, d))
+        }
+
+        Frame::EditTarget { axis, value: new } => {
+            Ok(Next::Return(edit_axis(&axis, &new, &value)?))
+        }
+
+        Frame::Hint { subject, hint, c } => {
+            vm.hint(&subject, &hint, &c)?;
+            if let Shape::Cell(tag, _) = hint.get() {
+                if is_trace_tag(tag) {
+                    trace.push(Noun::cell(tag.clone(), value.clone()));
+                    stack.push(Frame::TracePop);
+                }
+                if has_tag(tag, "fast") && vm.jets().is_some() {
+                    stack.push(Frame::Declare { clue: value });
+                }
+                return Ok(hinted(vm, spec, stack, subject, tag, c));
+            }
+            Ok(Next::Eval(subject, c))
+        }
+
+        Frame::Cons { subject, tail } => {
+            stack.push(Frame::ConsTail { head: value });
+            Ok(Next::Eval(subject, tail))
+        }
+
+        Frame::ConsTail { head } => Ok(Next::Return(Noun::cell(head, value))),
+
+        Frame::TracePop => {
+            trace.pop();
+            Ok(Next::Return(value))
+        }
+
+        Frame::JetCheck { core, axis, result } => {
+            if let Some(jets) = vm.jets() {
+                jets.check(&core, &axis, &result, &Ok(value.clone()));
+            }
+            Ok(Next::Return(value))
+        }
+
+        Frame::Declare { clue } => {
+            if let Some(jets) = vm.jets() {
+                jets.declare(&value, &clue);
+            }
+            Ok(Next::Return(value))
+        }
+
+        Frame::MemoStore { subject, formula } => {
+            if let Some(memo) = vm.memo() {
+                memo.insert(spec, subject, formula, value.clone());
+            }
+            Ok(Next::Return(value))
+        }
+    }
+}
+
+/// Start evaluating formula c under a hint with the given tag.
+fn hinted<N: Nock + ?Sized>(vm: &mut N,
+                            spec: NockSpec,
+                            stack: &mut Vec<Frame>,
+                            subject: Noun,
+                            tag: &Noun,
+                            c: Noun)
+                            -> Next {
+    if has_tag(tag, "memo") {
+        if let Some(memo) = vm.memo() {
+            if let Some(value) = memo.get(spec, &subject, &c) {
+                return Next::Return(value.clone());
+            }
+            stack.push(Frame::MemoStore {
+                subject: subject.clone(),
+                formula: c.clone(),
+            });
         }
     }
+    Next::Eval(subject, c)
 }
 
 /// Evaluate nock `/[axis subject]`
 pub fn get_axis(axis: &Noun, subject: &Noun) -> NockResult {
-    fn fas(x: &[u8], n: usize, mut subject: &Noun) -> NockResult {
+    /// Follow an axis of n bits, where right(i) is bit i of the axis.
+    fn fas<F>(n: usize, right: F, mut subject: &Noun) -> Option<&Noun>
+        where F: Fn(usize) -> bool
+    {
         for i in (0..(n - 1)).rev() {
-            if let Shape::Cell(ref a, ref b) = subject.get() {
-                if bit(x, i) {
+            if let Shape::Cell(a, b) = subject.get() {
+                if right(i) {
                     subject = b;
                 } else {
                     subject = a;
                 }
             } else {
-                return Err(NockError("axis".to_owned()));
+                return None;
             }
         }
-        Ok((*subject).clone())
+        Some(subject)
     }
 
-    #[inline]
-    fn bit(data: &[u8], pos: usize) -> bool {
-        data[pos / 8] & (1 << (pos % 8)) != 0
-    }
+    let found = match (axis.as_u64(), axis.get()) {
+        (Some(0), _) => None,
+        (Some(x), _) => {
+            fas((64 - x.leading_zeros()) as usize,
+                |i| x & (1 << i) != 0,
+                subject)
+        }
+        (None, Shape::Atom(x)) => fas(msb(x), |i| bit(x, i), subject),
+        _ => None,
+    };
+    found.cloned().ok_or_else(|| {
+        NockError::Axis {
+            axis: axis.clone(),
+            subject: subject.clone(),
+        }
+    })
+}
+
+/// Evaluate nock `#[axis value target]`
+///
+/// Produces a copy of target with the subnoun at axis replaced by value.
+pub fn edit_axis(axis: &Noun, value: &Noun, target: &Noun) -> NockResult {
+    let bad_axis = || {
+        Err(NockError::Axis {
+            axis: axis.clone(),
+            subject: target.clone(),
+        })
+    };
+    let x = match axis.get() {
+        Shape::Atom(x) if !x.is_empty() => x,
+        _ => return bad_axis(),
+    };
 
-    match axis.get() {
-        Shape::Atom(ref x) => {
-            let start = msb(x);
-            fas(x, start, subject)
+    // Walk down to the edited axis, remembering the siblings we pass.
+    let n = msb(x);
+    let mut path = Vec::with_capacity(n - 1);
+    let mut cur = target;
+    for i in (0..(n - 1)).rev() {
+        if let Shape::Cell(a, b) = cur.get() {
+            if bit(x, i) {
+                path.push((true, a));
+                cur = b;
+            } else {
+                path.push((false, b));
+                cur = a;
+            }
+        } else {
+            return bad_axis();
         }
-        _ => Err(NockError("axis".to_owned())),
     }
+    // Rebuild the spine back up with the new value in place.
+    Ok(path.into_iter().rev().fold(value.clone(), |acc, (right, sibling)| {
+        if right {
+            Noun::cell(sibling.clone(), acc)
+        } else {
+            Noun::cell(acc, sibling.clone())
+        }
+    }))
+}
+
+#[inline]
+fn bit(data: &[u8], pos: usize) -> bool {
+    data[pos / 8] & (1 << (pos % 8)) != 0
 }
@@ -0,0 +1,129 @@
+//! Hoon's orderings of nouns, used to build maps and sets.
+
+use std::cmp::Ordering;
+use {Shape, Noun};
+
+impl Noun {
+    /// Tree order, `(dor a b)`.
+    ///
+    /// Return whether `self` comes before or is equal to `other`. Atoms come
+    /// before cells and are ordered by value, cells are ordered by their
+    /// heads and then by their tails.
+    pub fn dor(&self, other: &Noun) -> bool {
+        let (mut a, mut b) = (self, other);
+        loop {
+            if a == b {
+                return true;
+            }
+            match (a.get(), b.get()) {
+                (Shape::Atom(x), Shape::Atom(y)) => {
+                    return cmp_atoms(x, y) == Ordering::Less
+                }
+                (Shape::Atom(_), Shape::Cell(..)) => return true,
+                (Shape::Cell(..), Shape::Atom(_)) => return false,
+                (Shape::Cell(p1, q1), Shape::Cell(p2, q2)) => {
+                    if p1 == p2 {
+                        a = q1;
+                        b = q2;
+                    } else {
+                        a = p1;
+                        b = p2;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Mug order, `(gor a b)`.
+    ///
+    /// Nouns are ordered by their mugs, and by tree order if the mugs are
+    /// the same. This is the order of the keys in a Hoon map or set.
+    pub fn gor(&self, other: &Noun) -> bool {
+        let (c, d) = (self.mug(), other.mug());
+        if c == d { self.dor(other) } else { c < d }
+    }
+
+    /// Double mug order, `(mor a b)`.
+    ///
+    /// Nouns are ordered by the mugs of their mugs, and by tree order if
+    /// those are the same. This is the heap order of the nodes in a Hoon
+    /// map or set.
+    pub fn mor(&self, other: &Noun) -> bool {
+        let c = Noun::from(self.mug()).mug();
+        let d = Noun::from(other.mug()).mug();
+        if c == d { self.dor(other) } else { c < d }
+    }
+}
+
+/// Compare atoms given as little-endian digits without trailing zeros.
+fn cmp_atoms(a: &[u8], b: &[u8]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+#[cfg(test)]
+mod tests {
+    use {Noun, ToNoun};
+
+    fn noun(s: &str) -> Noun {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_dor() {
+        assert!(noun("1").dor(&noun("2")));
+        assert!(!noun("2").dor(&noun("1")));
+        assert!(noun("2").dor(&noun("2")));
+        assert!(noun("255").dor(&noun("256")));
+        assert!(!noun("18446744073709551616").dor(&noun("256")));
+        assert!(noun("18446744073709551616").dor(&noun("[0 0]")));
+        assert!(!noun("[0 0]").dor(&noun("18446744073709551616")));
+        assert!(noun("[1 2]").dor(&noun("[2 1]")));
+        assert!(noun("[1 2]").dor(&noun("[1 3]")));
+        assert!(!noun("[1 3]").dor(&noun("[1 2]")));
+        assert!(noun("[1 2]").dor(&noun("[[0 0] 0]")));
+        assert!(noun("[[1 2] 3]").dor(&noun("[[1 2] 3]")));
+    }
+
+    #[test]
+    fn test_gor_mor() {
+        let (a, b) = (noun("1"), noun("2"));
+        assert_eq!(a.gor(&b), a.mug() < b.mug());
+        assert_eq!(b.gor(&a), b.mug() < a.mug());
+        assert!(a.gor(&a));
+
+        let (c, d) = (noun("[1 2]"), noun("[3 4]"));
+        let (mc, md) = (Noun::from(c.mug()).mug(), Noun::from(d.mug()).mug());
+        assert_eq!(c.mor(&d), mc < md);
+        assert_eq!(d.mor(&c), md < mc);
+        assert!(c.mor(&c));
+    }
+
+    #[test]
+    fn test_urbit_order() {
+        // The nouns of the Urbit mug vectors in `test_mug`, sorted by their
+        // mugs and by the mugs of their mugs.
+        let hello = "Hello, world!".to_noun();
+        let gor = [Noun::cell(noun("0"), hello.clone()), // 0x056c_adc8
+                   noun("[0 0]"),                        // 0x192f_5588
+                   noun("[1 2 3]"),                      // 0x3a81_1aec
+                   hello.clone(),                        // 0x4d44_1035
+                   Noun::cell(hello.clone(), noun("0")), // 0x6f29_47af
+                   noun("1"),                            // 0x715c_2a60
+                   noun("0")];                           // 0x79ff_04e8
+        let mor = [noun("[0 0]"),                        // 0x0e62_ef53
+                   hello.clone(),                        // 0x311b_f2f5
+                   noun("0"),                            // 0x3699_4f2a
+                   noun("1"),                            // 0x53c0_2d4d
+                   Noun::cell(noun("0"), hello.clone()), // 0x5be5_6ab2
+                   noun("[1 2 3]"),                      // 0x661a_1658
+                   Noun::cell(hello, noun("0"))];        // 0x791a_6109
+        for i in 0..gor.len() {
+            for j in i + 1..gor.len() {
+                assert!(gor[i].gor(&gor[j]) && !gor[j].gor(&gor[i]));
+                assert!(mor[i].mor(&mor[j]) && !mor[j].mor(&mor[i]));
+            }
+        }
+        assert_eq!(Noun::from(noun("[0 0]").mug()).mug(), 0x0e62_ef53);
+        assert_eq!(Noun::from(noun("1").mug()).mug(), 0x53c0_2d4d);
+    }
+}
@@ -0,0 +1,315 @@
+//! SHA-2 hashes with Urbit's conventions for atoms.
+//!
+//! Urbit hashes the little-endian bytes of an atom, and reads the digest
+//! back as a little-endian atom.
+
+use {Shape, Noun, NockError, NockResult};
+
+impl Noun {
+    /// SHA-256 of an atom, Hoon's `shax`.
+    pub fn shax(&self) -> NockResult {
+        let x = atom_digits(self)?;
+        Ok(digest_atom(&sha256(x)))
+    }
+
+    /// SHA-256 of the first `len` bytes of an atom, Hoon's `shay`.
+    ///
+    /// Fails if `len` doesn't fit in 31 bits, like in Vere.
+    pub fn shay(&self, len: usize) -> NockResult {
+        Ok(digest_atom(&sha256(&padded(atom_digits(self)?, len)?)))
+    }
+
+    /// SHA-512 of the first `len` bytes of an atom, Hoon's `shal`.
+    ///
+    /// Fails if `len` doesn't fit in 31 bits, like in Vere.
+    pub fn shal(&self, len: usize) -> NockResult {
+        Ok(digest_atom(&sha512(&padded(atom_digits(self)?, len)?)))
+    }
+
+    /// Salted SHA-256 of an atom, Hoon's `shas`.
+    pub fn shas(&self, salt: &Noun) -> NockResult {
+        let hash = self.shax()?;
+        let salt = atom_digits(salt)?;
+        let hash = atom_digits(&hash)?;
+        let mut mixed = vec![0; salt.len().max(hash.len())];
+        for (i, x) in mixed.iter_mut().enumerate() {
+            *x = salt.get(i).cloned().unwrap_or(0) ^
+                 hash.get(i).cloned().unwrap_or(0);
+        }
+        let len = mixed.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        Ok(digest_atom(&sha256(&mixed[..len])))
+    }
+}
+
+fn atom_digits(noun: &Noun) -> Result<&[u8], NockError> {
+    match noun.get() {
+        Shape::Atom(x) => Ok(x),
+        _ => Err(NockError::conversion::<[u8]>(noun)),
+    }
+}
+
+/// Largest length `shay` and `shal` will pad an atom to.
+///
+/// Vere only takes lengths that fit in a direct atom, 31 bits.
+const MAX_LEN: usize = 0x7fff_ffff;
+
+/// The first `len` bytes of an atom, zero-padded.
+fn padded(x: &[u8], len: usize) -> Result<Vec<u8>, NockError> {
+    if len > MAX_LEN {
+        return Err(NockError::Other(format!("hash length {} too large",
+                                            len)));
+    }
+    let mut ret = x[..len.min(x.len())].to_vec();
+    ret.resize(len, 0);
+    Ok(ret)
+}
+
+fn digest_atom(digest: &[u8]) -> Noun {
+    let len = digest.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    Noun::atom(&digest[..len])
+}
+
+const K256: [u32; 64] =
+    [0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+     0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+     0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+     0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+     0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+     0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+     0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+     0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+     0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+     0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+     0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2];
+
+const K512: [u64; 80] =
+    [0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f,
+     0xe9b5dba58189dbbc, 0x3956c25bf348b538, 0x59f111f1b605d019,
+     0x923f82a4af194f9b, 0xab1c5ed5da6d8118, 0xd807aa98a3030242,
+     0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+     0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235,
+     0xc19bf174cf692694, 0xe49b69c19ef14ad2, 0xefbe4786384f25e3,
+     0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65, 0x2de92c6f592b0275,
+     0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+     0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f,
+     0xbf597fc7beef0ee4, 0xc6e00bf33da88fc2, 0xd5a79147930aa725,
+     0x06ca6351e003826f, 0x142929670a0e6e70, 0x27b70a8546d22ffc,
+     0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+     0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6,
+     0x92722c851482353b, 0xa2bfe8a14cf10364, 0xa81a664bbc423001,
+     0xc24b8b70d0f89791, 0xc76c51a30654be30, 0xd192e819d6ef5218,
+     0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+     0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99,
+     0x34b0bcb5e19b48a8, 0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb,
+     0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3, 0x748f82ee5defb2fc,
+     0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+     0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915,
+     0xc67178f2e372532b, 0xca273eceea26619c, 0xd186b8c721c0c207,
+     0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178, 0x06f067aa72176fba,
+     0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+     0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc,
+     0x431d67c49c100d4c, 0x4cc5d4becb3e42b6, 0x597f299cfc657e2a,
+     0x5fcb6fab3ad6faec, 0x6c44198c4a475817];
+
+/// Append the SHA-2 padding for a message in blocks of `block` bytes, with
+/// a `len_bytes`-byte big-endian bit length.
+fn pad(data: &[u8], block: usize, len_bytes: usize) -> Vec<u8> {
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % block != block - len_bytes {
+        msg.push(0);
+    }
+    let bits = (data.len() as u128) * 8;
+    msg.extend_from_slice(&bits.to_be_bytes()[16 - len_bytes..]);
+    msg
+}
+
+/// SHA-256 digest of a byte string.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+                           0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+    for chunk in pad(data, 64, 8).chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^
+                     (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^
+                     (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                       .wrapping_add(s0)
+                       .wrapping_add(w[i - 7])
+                       .wrapping_add(s1);
+        }
+
+        let mut v = h;
+        for i in 0..64 {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^
+                     v[4].rotate_right(25);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7]
+                         .wrapping_add(s1)
+                         .wrapping_add(ch)
+                         .wrapping_add(K256[i])
+                         .wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^
+                     v[0].rotate_right(22);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v = [t1.wrapping_add(t2),
+                 v[0],
+                 v[1],
+                 v[2],
+                 v[3].wrapping_add(t1),
+                 v[4],
+                 v[5],
+                 v[6]];
+        }
+        for (x, y) in h.iter_mut().zip(v.iter()) {
+            *x = x.wrapping_add(*y);
+        }
+    }
+
+    let mut ret = [0u8; 32];
+    for (out, x) in ret.chunks_mut(4).zip(h.iter()) {
+        out.copy_from_slice(&x.to_be_bytes());
+    }
+    ret
+}
+
+/// SHA-512 digest of a byte string.
+pub fn sha512(data: &[u8]) -> [u8; 64] {
+    let mut h: [u64; 8] = [0x6a09e667f3bcc908,
+                           0xbb67ae8584caa73b,
+                           0x3c6ef372fe94f82b,
+                           0xa54ff53a5f1d36f1,
+                           0x510e527fade682d1,
+                           0x9b05688c2b3e6c1f,
+                           0x1f83d9abfb41bd6b,
+                           0x5be0cd19137e2179];
+
+    for chunk in pad(data, 128, 16).chunks(128) {
+        let mut w = [0u64; 80];
+        for (i, word) in chunk.chunks(8).enumerate() {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(word);
+            w[i] = u64::from_be_bytes(bytes);
+        }
+        for i in 16..80 {
+            let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^
+                     (w[i - 15] >> 7);
+            let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^
+                     (w[i - 2] >> 6);
+            w[i] = w[i - 16]
+                       .wrapping_add(s0)
+                       .wrapping_add(w[i - 7])
+                       .wrapping_add(s1);
+        }
+
+        let mut v = h;
+        for i in 0..80 {
+            let s1 = v[4].rotate_right(14) ^ v[4].rotate_right(18) ^
+                     v[4].rotate_right(41);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7]
+                         .wrapping_add(s1)
+                         .wrapping_add(ch)
+                         .wrapping_add(K512[i])
+                         .wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(28) ^ v[0].rotate_right(34) ^
+                     v[0].rotate_right(39);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v = [t1.wrapping_add(t2),
+                 v[0],
+                 v[1],
+                 v[2],
+                 v[3].wrapping_add(t1),
+                 v[4],
+                 v[5],
+                 v[6]];
+        }
+        for (x, y) in h.iter_mut().zip(v.iter()) {
+            *x = x.wrapping_add(*y);
+        }
+    }
+
+    let mut ret = [0u8; 64];
+    for (out, x) in ret.chunks_mut(8).zip(h.iter()) {
+        out.copy_from_slice(&x.to_be_bytes());
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use {Noun, ToNoun};
+    use super::{sha256, sha512, MAX_LEN};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Atom whose little-endian bytes are the given hex digest.
+    fn digest(hex: &str) -> Noun {
+        let bytes: Vec<u8> = (0..hex.len() / 2)
+                                 .map(|i| {
+                                     u8::from_str_radix(&hex[2 * i..2 * i + 2],
+                                                        16)
+                                         .unwrap()
+                                 })
+                                 .collect();
+        Noun::atom(&bytes)
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(hex(&sha256(b"")),
+                   "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex(&sha256(b"abc")),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+                   "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(hex(&sha256(&[b'a'; 1000])),
+                   "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3");
+    }
+
+    #[test]
+    fn test_sha512() {
+        assert_eq!(hex(&sha512(b"")),
+                   "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+                    47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e");
+        assert_eq!(hex(&sha512(b"abc")),
+                   "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                    2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f");
+    }
+
+    #[test]
+    fn test_atoms() {
+        let abc = "abc".to_noun();
+        assert_eq!(abc.shax(),
+                   Ok(digest("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb4\
+                              10ff61f20015ad")));
+        assert_eq!(abc.shay(3), abc.shax());
+        // Short lengths cut the atom, long ones pad it with zero bytes.
+        assert_eq!(abc.shay(2), "ab".to_noun().shax());
+        assert_eq!(abc.shay(4), Ok(digest(&hex(&sha256(b"abc\0")))));
+        // Lengths past a direct atom are refused before padding.
+        assert!(abc.shay(1 << 62).is_err());
+        assert!(abc.shal(MAX_LEN + 1).is_err());
+        assert_eq!(Noun::from(0u32).shax(),
+                   Ok(digest("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934c\
+                              a495991b7852b855")));
+        assert_eq!(abc.shal(3),
+                   Ok(digest("ddaf35a193617abacc417349ae20413112e6fa4e89a97ea2\
+                              0a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd\
+                              454d4423643ce80e2a9ac94fa54ca49f")));
+        // Salting mixes the salt into the hash of the atom.
+        assert_eq!(abc.shas(&Noun::from(0u32)), abc.shax().unwrap().shax());
+        assert!(abc.shas(&"salt".to_noun()) != abc.shas(&Noun::from(0u32)));
+        assert!(Noun::cell(abc.clone(), abc.clone()).shax().is_err());
+    }
+}
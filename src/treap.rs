@@ -0,0 +1,282 @@
+//! Conversions between Rust collections and Hoon maps and sets.
+//!
+//! Hoon maps and sets are treaps, trees of `[n l r]` nodes where `~` is the
+//! empty tree. The keys are in search tree order by `gor` and in heap order by
+//! `mor`, so every set of keys has exactly one layout.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+use {FromNoun, NockError, Noun, ToNoun};
+
+/// Build a treap from `(key, node)` pairs.
+fn treap(mut nodes: Vec<(Noun, Noun)>) -> Noun {
+    nodes.sort_by(|a, b| order(Noun::gor, &a.0, &b.0));
+    nodes.dedup_by(|a, b| a.0 == b.0);
+    build(&nodes)
+}
+
+/// Build a treap from nodes sorted by key.
+fn build(nodes: &[(Noun, Noun)]) -> Noun {
+    // The stack is the right spine of the tree of the nodes so far. The next
+    // node goes at the end of the spine, below the nodes before it in heap
+    // order, and the nodes it passes become its left subtree.
+    let mut left = vec![None; nodes.len()];
+    let mut right = vec![None; nodes.len()];
+    let mut spine: Vec<usize> = Vec::new();
+    for i in 0..nodes.len() {
+        let mut below = None;
+        while let Some(&top) = spine.last() {
+            if !Noun::mor(&nodes[i].0, &nodes[top].0) {
+                break;
+            }
+            below = spine.pop();
+        }
+        left[i] = below;
+        if let Some(&top) = spine.last() {
+            right[top] = Some(i);
+        }
+        spine.push(i);
+    }
+
+    // Put the nouns together from the leaves up.
+    let mut done: Vec<Option<Noun>> = vec![None; nodes.len()];
+    let tree = |done: &mut Vec<Option<Noun>>, i: Option<usize>| {
+        i.map_or(Noun::from(0u32), |i| done[i].take().unwrap())
+    };
+    let root = spine.first().cloned();
+    let mut stack: Vec<_> = root.map(|i| (i, false)).into_iter().collect();
+    while let Some((i, expanded)) = stack.pop() {
+        if expanded {
+            let l = tree(&mut done, left[i]);
+            let r = tree(&mut done, right[i]);
+            done[i] = Some(Noun::cell(nodes[i].1.clone(), Noun::cell(l, r)));
+        } else {
+            stack.push((i, true));
+            stack.extend(left[i].map(|x| (x, false)));
+            stack.extend(right[i].map(|x| (x, false)));
+        }
+    }
+    tree(&mut done, root)
+}
+
+/// Turn one of the Hoon orderings into an `Ordering`.
+fn order(f: fn(&Noun, &Noun) -> bool, a: &Noun, b: &Noun) -> Ordering {
+    if a == b {
+        Ordering::Equal
+    } else if f(a, b) {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+/// Collect the nodes of a treap, or `None` if the noun is not a tree.
+fn nodes(noun: &Noun) -> Option<Vec<&Noun>> {
+    let mut ret = Vec::new();
+    let mut stack = vec![noun];
+    while let Some(x) = stack.pop() {
+        if x == &Noun::from(0u32) {
+            continue;
+        }
+        let (n, l, r) = x.get_122()?;
+        ret.push(n);
+        stack.push(r);
+        stack.push(l);
+    }
+    Some(ret)
+}
+
+fn map_node<K: ToNoun, V: ToNoun>(k: &K, v: &V) -> (Noun, Noun) {
+    let k = k.to_noun();
+    (k.clone(), Noun::cell(k, v.to_noun()))
+}
+
+fn set_node<T: ToNoun>(x: &T) -> (Noun, Noun) {
+    let x = x.to_noun();
+    (x.clone(), x)
+}
+
+impl<K, V, S> ToNoun for HashMap<K, V, S>
+    where K: ToNoun,
+          V: ToNoun
+{
+    fn to_noun(&self) -> Noun {
+        treap(self.iter().map(|(k, v)| map_node(k, v)).collect())
+    }
+}
+
+impl<K, V, S> FromNoun for HashMap<K, V, S>
+    where K: FromNoun + Eq + Hash,
+          V: FromNoun,
+          S: BuildHasher + Default
+{
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        nodes(n).ok_or_else(|| NockError::conversion::<Self>(n))?
+                .into_iter()
+                .map(FromNoun::from_noun)
+                .collect()
+    }
+}
+
+impl<K, V> ToNoun for BTreeMap<K, V>
+    where K: ToNoun,
+          V: ToNoun
+{
+    fn to_noun(&self) -> Noun {
+        treap(self.iter().map(|(k, v)| map_node(k, v)).collect())
+    }
+}
+
+impl<K, V> FromNoun for BTreeMap<K, V>
+    where K: FromNoun + Ord,
+          V: FromNoun
+{
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        nodes(n).ok_or_else(|| NockError::conversion::<Self>(n))?
+                .into_iter()
+                .map(FromNoun::from_noun)
+                .collect()
+    }
+}
+
+impl<T, S> ToNoun for HashSet<T, S>
+    where T: ToNoun
+{
+    fn to_noun(&self) -> Noun {
+        treap(self.iter().map(set_node).collect())
+    }
+}
+
+impl<T, S> FromNoun for HashSet<T, S>
+    where T: FromNoun + Eq + Hash,
+          S: BuildHasher + Default
+{
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        nodes(n).ok_or_else(|| NockError::conversion::<Self>(n))?
+                .into_iter()
+                .map(FromNoun::from_noun)
+                .collect()
+    }
+}
+
+impl<T> ToNoun for BTreeSet<T>
+    where T: ToNoun
+{
+    fn to_noun(&self) -> Noun {
+        treap(self.iter().map(set_node).collect())
+    }
+}
+
+impl<T> FromNoun for BTreeSet<T>
+    where T: FromNoun + Ord
+{
+    fn from_noun(n: &Noun) -> Result<Self, NockError> {
+        nodes(n).ok_or_else(|| NockError::conversion::<Self>(n))?
+                .into_iter()
+                .map(FromNoun::from_noun)
+                .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+    use {FromNoun, Noun, ToNoun};
+
+    /// `put:by` and `put:in` from `hoon.hoon`, inserting node `n` with key
+    /// `b`, where `key` gets the key of a node.
+    fn put(a: &Noun, b: &Noun, n: Noun, key: fn(&Noun) -> Noun) -> Noun {
+        let node = |n: Noun, l: Noun, r: Noun| Noun::cell(n, Noun::cell(l, r));
+        let (na, l, r) = match a.get_122() {
+            None => return node(n, Noun::from(0u32), Noun::from(0u32)),
+            Some((na, l, r)) => (na.clone(), l.clone(), r.clone()),
+        };
+        let p = key(&na);
+
+        if *b == p {
+            return node(n, l, r);
+        }
+        if b.gor(&p) {
+            let d = put(&l, b, n, key);
+            let (nd, ld, rd) = d.get_122().unwrap();
+            if p.mor(&key(nd)) {
+                node(na, d.clone(), r)
+            } else {
+                node(nd.clone(), ld.clone(), node(na, rd.clone(), r))
+            }
+        } else {
+            let d = put(&r, b, n, key);
+            let (nd, ld, rd) = d.get_122().unwrap();
+            if p.mor(&key(nd)) {
+                node(na, l, d.clone())
+            } else {
+                node(nd.clone(), node(na, l, ld.clone()), rd.clone())
+            }
+        }
+    }
+
+    fn map_key(n: &Noun) -> Noun {
+        <(Noun, Noun)>::from_noun(n).unwrap().0
+    }
+
+    fn set_key(n: &Noun) -> Noun {
+        n.clone()
+    }
+
+    #[test]
+    fn test_map() {
+        let mut map = HashMap::new();
+        let mut by = Noun::from(0u32);
+        for i in 0..100u32 {
+            by = put(&by,
+                     &Noun::from(i),
+                     Noun::cell(Noun::from(i), Noun::from(i * i)),
+                     map_key);
+            map.insert(i, i * i);
+        }
+        assert_eq!(map.to_noun(), by);
+        assert_eq!(HashMap::from_noun(&by), Ok(map.clone()));
+
+        let tree: BTreeMap<u32, u32> = map.into_iter().collect();
+        assert_eq!(tree.to_noun(), by);
+        assert_eq!(BTreeMap::from_noun(&by), Ok(tree));
+
+        let empty: HashMap<u32, u32> = HashMap::new();
+        assert_eq!(empty.to_noun(), Noun::from(0u32));
+        assert_eq!(HashMap::from_noun(&Noun::from(0u32)), Ok(empty));
+        assert!(HashMap::<u32, u32>::from_noun(&Noun::from(1u32)).is_err());
+        assert!(HashMap::<u32, u32>::from_noun(&"[[1 2] 0]"
+                                                     .parse()
+                                                     .unwrap())
+                    .is_err());
+    }
+
+    #[test]
+    fn test_set() {
+        let set: HashSet<u32> = (0..100).map(|x| x * 7).collect();
+        let mut in_ = Noun::from(0u32);
+        for i in (0..100).rev() {
+            let x = Noun::from(i * 7u32);
+            in_ = put(&in_, &x, x.clone(), set_key);
+        }
+        assert_eq!(set.to_noun(), in_);
+        assert_eq!(HashSet::from_noun(&in_), Ok(set.clone()));
+
+        let tree: BTreeSet<u32> = set.into_iter().collect();
+        assert_eq!(tree.to_noun(), in_);
+        assert_eq!(BTreeSet::from_noun(&in_), Ok(tree));
+    }
+
+    #[test]
+    fn test_large() {
+        let set: BTreeSet<u32> = (0..5_000).map(|x| x * 13 % 7_919).collect();
+        let mut in_ = Noun::from(0u32);
+        for &i in &set {
+            let x = Noun::from(i);
+            in_ = put(&in_, &x, x.clone(), set_key);
+        }
+        assert_eq!(set.to_noun(), in_);
+        assert_eq!(BTreeSet::from_noun(&in_), Ok(set));
+    }
+}